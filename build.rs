@@ -0,0 +1,36 @@
+//! Exposes build information to the binary for `--version`:
+//! `AFT_GIT_HASH`, `AFT_BUILD_TIMESTAMP` and `AFT_SOLANA_SDK_VERSION`.
+
+use std::{fs, process::Command, time::SystemTime};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=AFT_GIT_HASH={}", git_hash);
+
+    let timestamp = humantime::format_rfc3339_seconds(SystemTime::now());
+    println!("cargo:rustc-env=AFT_BUILD_TIMESTAMP={}", timestamp);
+
+    let sdk_version = locked_version("solana-sdk").unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=AFT_SOLANA_SDK_VERSION={}", sdk_version);
+}
+
+/// Version of `package` resolved in Cargo.lock, if the lock file is present.
+fn locked_version(package: &str) -> Option<String> {
+    let lock = fs::read_to_string("Cargo.lock").ok()?;
+    let name_line = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name_line)?;
+    let version = lines.next()?.strip_prefix("version = \"")?;
+    Some(version.trim_end_matches('"').to_string())
+}
@@ -0,0 +1,94 @@
+//! Formatting of SOL amounts in logs, notifications and reports (`sol_decimals`).
+//!
+//! Amounts are formatted from lamports with integer arithmetic, so they never show float noise
+//! such as `7.000000001` for 7 SOL.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::LAMPORTS_PER_SOL;
+
+/// Decimal places of one lamport; the default and the maximum of `sol_decimals`.
+pub const MAX_DECIMALS: usize = 9;
+
+static DECIMALS: AtomicUsize = AtomicUsize::new(MAX_DECIMALS);
+
+/// Show SOL amounts with `decimals` decimal places (at most `MAX_DECIMALS`) for the rest of
+/// the process.
+pub fn set_decimals(decimals: usize) {
+    DECIMALS.store(decimals.min(MAX_DECIMALS), Ordering::Relaxed);
+}
+
+/// A lamport amount shown in SOL, rounded to `sol_decimals` places.
+#[derive(Debug, Clone, Copy)]
+pub struct Sol(u64);
+
+/// Show `lamports` in SOL.
+pub fn sol(lamports: u64) -> Sol {
+    Sol(lamports)
+}
+
+impl fmt::Display for Sol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_sol(self.0, DECIMALS.load(Ordering::Relaxed), f)
+    }
+}
+
+/// A lamport amount shown in SOL with the exact lamport value alongside, for transfers:
+/// `2.0000 SOL (2000000001 lamports)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Exact(u64);
+
+/// Show `lamports` in SOL and in lamports.
+pub fn exact(lamports: u64) -> Exact {
+    Exact(lamports)
+}
+
+impl fmt::Display for Exact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} SOL ({} lamports)", Sol(self.0), self.0)
+    }
+}
+
+/// Write `lamports` in SOL, rounded half up to `decimals` places.
+fn format_sol(lamports: u64, decimals: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let unit = 10u128.pow((MAX_DECIMALS - decimals) as u32);
+    let scaled = (lamports as u128 + unit / 2) / unit;
+    let per_sol = LAMPORTS_PER_SOL as u128 / unit;
+    if decimals == 0 {
+        write!(f, "{}", scaled)
+    } else {
+        write!(
+            f,
+            "{}.{:0width$}",
+            scaled / per_sol,
+            scaled % per_sol,
+            width = decimals
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(u64, usize);
+
+    impl fmt::Display for Fixed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            format_sol(self.0, self.1, f)
+        }
+    }
+
+    #[test]
+    fn sol_amounts_are_rounded_to_the_configured_places() {
+        assert_eq!(Fixed(7_000_000_001, 9).to_string(), "7.000000001");
+        assert_eq!(Fixed(7_000_000_001, 4).to_string(), "7.0000");
+        assert_eq!(Fixed(6_999_950_000, 4).to_string(), "7.0000");
+        assert_eq!(Fixed(1_500_000, 2).to_string(), "0.00");
+        assert_eq!(Fixed(2_500_000_000, 0).to_string(), "3");
+        assert_eq!(Fixed(u64::MAX, 9).to_string(), "18446744073.709551615");
+    }
+}
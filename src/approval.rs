@@ -0,0 +1,164 @@
+//! Manual approval of large transfers (`approval_required_above_sol`).
+//!
+//! The daemon announces each pending transfer with a request id on the notification channel,
+//! then polls the approval source until the transfer is approved, rejected or the wait times out:
+//!
+//! - `approval_file`: approved once a line of the file reads `<id>`, rejected on `reject <id>`.
+//! - `approval_url`: `GET <url>?request=<id>`; 200 approves, 403 rejects, anything else
+//!   (404, errors) keeps waiting.
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::StatusCode;
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::USER_AGENT;
+
+/// Delay between two looks at the approval source.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where approvals come from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    File(PathBuf),
+    Url(reqwest::Url),
+}
+
+/// Outcome of waiting for an approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approved,
+    Rejected,
+    TimedOut,
+}
+
+/// The approval gate: transfers above `threshold_lamports` wait for `source`.
+#[derive(Debug, Clone)]
+pub struct Approval {
+    pub threshold_lamports: u64,
+    source: Source,
+    pub timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl Approval {
+    pub fn new(
+        threshold_lamports: u64,
+        source: Source,
+        timeout: Duration,
+        request_timeout: Duration,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .use_rustls_tls()
+            .user_agent(USER_AGENT)
+            .timeout(request_timeout)
+            .build()
+            .context("building approval HTTP client")?;
+        Ok(Self {
+            threshold_lamports,
+            source,
+            timeout,
+            client,
+        })
+    }
+
+    /// How to approve request `id`, for the approval request notification.
+    pub fn instructions(&self, id: &str) -> String {
+        match &self.source {
+            Source::File(path) => format!(
+                "add the line `{}` to {} (or `reject {}`)",
+                id,
+                path.display(),
+                id
+            ),
+            Source::Url(url) => format!("approve request {} at {}", id, url),
+        }
+    }
+
+    /// Poll the source until request `id` is decided or `timeout` passes. Source errors are
+    /// logged and the wait goes on.
+    pub async fn wait(&self, id: &str) -> Decision {
+        let started = Instant::now();
+        loop {
+            match self.check(id).await {
+                Ok(Some(true)) => return Decision::Approved,
+                Ok(Some(false)) => return Decision::Rejected,
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read approval source: {:#}", e),
+            }
+            if started.elapsed() >= self.timeout {
+                return Decision::TimedOut;
+            }
+            sleep(POLL_INTERVAL.min(self.timeout.saturating_sub(started.elapsed()))).await;
+        }
+    }
+
+    /// `Some(true)` if `id` is approved, `Some(false)` if rejected, `None` while undecided.
+    async fn check(&self, id: &str) -> Result<Option<bool>> {
+        match &self.source {
+            Source::File(path) => match fs::read_to_string(path) {
+                Ok(text) => Ok(file_decision(&text, id)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => {
+                    Err(e).with_context(|| format!("reading approval file {}", path.display()))
+                }
+            },
+            Source::Url(url) => {
+                let mut url = url.clone();
+                url.query_pairs_mut().append_pair("request", id);
+                let resp = self
+                    .client
+                    .get(url.clone())
+                    .send()
+                    .await
+                    .with_context(|| format!("polling approval URL {}", url))?;
+                Ok(match resp.status() {
+                    StatusCode::OK => Some(true),
+                    StatusCode::FORBIDDEN => Some(false),
+                    StatusCode::NOT_FOUND => None,
+                    status => return Err(anyhow!("approval URL {} returned {}", url, status)),
+                })
+            }
+        }
+    }
+}
+
+/// Decision on `id` in approval file contents; a rejection wins over an approval.
+fn file_decision(text: &str, id: &str) -> Option<bool> {
+    let mut decision = None;
+    for line in text.lines().map(str::trim) {
+        if line.strip_prefix("reject ").map(str::trim) == Some(id) {
+            return Some(false);
+        }
+        if line == id {
+            decision = Some(true);
+        }
+    }
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approval_file_lines_approve_or_reject_a_request() {
+        assert_eq!(file_decision("", "sweep-3-100"), None);
+        assert_eq!(
+            file_decision("sweep-2-100\nsweep-3-100\n", "sweep-3-100"),
+            Some(true)
+        );
+        // Approvals are per request id, which includes the amount
+        assert_eq!(file_decision("sweep-3-99\n", "sweep-3-100"), None);
+        assert_eq!(
+            file_decision("sweep-3-100\nreject sweep-3-100\n", "sweep-3-100"),
+            Some(false)
+        );
+    }
+}
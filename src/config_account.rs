@@ -0,0 +1,88 @@
+//! Centrally managed settings read from an on-chain account (`config_account`).
+//!
+//! Account data layout (little-endian, 24 bytes; trailing bytes are ignored):
+//!
+//! | offset | size | field                                              |
+//! |--------|------|----------------------------------------------------|
+//! | 0      | 8    | magic `AFTCFG01`                                   |
+//! | 8      | 8    | threshold in lamports (`u64`, 0 = use local)       |
+//! | 16     | 8    | poll interval in seconds (`u64`, 0 = use local)    |
+
+use anyhow::{Result, anyhow};
+use std::time::Duration;
+
+/// Leading bytes of a config account, guarding against pointing at an unrelated account.
+pub const MAGIC: &[u8; 8] = b"AFTCFG01";
+
+/// Length of the documented layout.
+const LAYOUT_LENGTH: usize = 24;
+
+/// Settings decoded from a config account; `None` leaves the local setting in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnChainConfig {
+    pub threshold_lamports: Option<u64>,
+    pub poll_interval: Option<Duration>,
+}
+
+/// Decode config account data.
+pub fn parse(data: &[u8]) -> Result<OnChainConfig> {
+    if data.len() < LAYOUT_LENGTH {
+        return Err(anyhow!(
+            "config account holds {} bytes, expected at least {}",
+            data.len(),
+            LAYOUT_LENGTH
+        ));
+    }
+    if &data[..8] != MAGIC {
+        return Err(anyhow!(
+            "config account data does not start with {:?}",
+            String::from_utf8_lossy(MAGIC)
+        ));
+    }
+    let field = |offset: usize| {
+        let value = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        (value != 0).then_some(value)
+    };
+    Ok(OnChainConfig {
+        threshold_lamports: field(8),
+        poll_interval: field(16).map(Duration::from_secs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_data(threshold: u64, interval: u64) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&threshold.to_le_bytes());
+        data.extend_from_slice(&interval.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn layout_is_decoded_and_zero_means_local() {
+        assert_eq!(
+            parse(&account_data(5_000_000_000, 3600)).unwrap(),
+            OnChainConfig {
+                threshold_lamports: Some(5_000_000_000),
+                poll_interval: Some(Duration::from_secs(3600)),
+            }
+        );
+        assert_eq!(
+            parse(&account_data(0, 0)).unwrap(),
+            OnChainConfig {
+                threshold_lamports: None,
+                poll_interval: None,
+            }
+        );
+    }
+
+    #[test]
+    fn short_or_foreign_data_is_rejected() {
+        assert!(parse(&account_data(1, 1)[..20]).is_err());
+        let mut data = account_data(1, 1);
+        data[0] = b'X';
+        assert!(parse(&data).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Integrity monitoring of the config files (`config_watch_interval_seconds`): each file is
+//! hashed at startup and re-hashed periodically, and a change on disk is alerted with the old and
+//! new SHA-256 hashes. The running daemon keeps the config it started with.
+
+use solana_sdk::hash::hash;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The config files and their last seen hashes.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    files: Vec<(PathBuf, Option<String>)>,
+}
+
+impl Watch {
+    /// Hash `paths` as they are now.
+    pub fn new(paths: &[String]) -> Self {
+        Self {
+            files: paths
+                .iter()
+                .map(|path| {
+                    let path = PathBuf::from(path);
+                    let digest = digest(&path);
+                    (path, digest)
+                })
+                .collect(),
+        }
+    }
+
+    /// Re-hash the files and describe each that changed since the last check (an unreadable or
+    /// deleted file hashes as "missing"). Each change is reported once.
+    pub fn check(&mut self) -> Vec<String> {
+        let mut changes = Vec::new();
+        for (path, last) in &mut self.files {
+            let current = digest(path);
+            if current != *last {
+                changes.push(format!(
+                    "{}: sha256 {} -> {}",
+                    path.display(),
+                    last.as_deref().unwrap_or("missing"),
+                    current.as_deref().unwrap_or("missing")
+                ));
+                *last = current;
+            }
+        }
+        changes
+    }
+}
+
+/// Hex SHA-256 of the file at `path`, as printed by `sha256sum`; `None` if it cannot be read.
+fn digest(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(
+        hash(&bytes)
+            .to_bytes()
+            .iter()
+            .fold(String::new(), |mut hex, b| {
+                let _ = write!(hex, "{:02x}", b);
+                hex
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_changed_config_file_is_reported_once() {
+        let path =
+            std::env::temp_dir().join(format!("aft-config-watch-{}.toml", std::process::id()));
+        fs::write(&path, "sol_threshold = 7.0\n").unwrap();
+        let mut watch = Watch::new(&[path.display().to_string()]);
+        assert!(watch.check().is_empty());
+
+        fs::write(&path, "sol_threshold = 1.0\n").unwrap();
+        assert_eq!(
+            watch.check(),
+            [format!(
+                "{}: sha256 {} -> {}",
+                path.display(),
+                "a6b4a059660f042ee586a744cdd4c3df959482b19909e74b44fea8802cf20a08",
+                "cc3724613f259dbb7faf5a1475c3a60e218dd9bce3dae08d05f20685a88362da"
+            )]
+        );
+        assert!(watch.check().is_empty());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watch.check()[0].ends_with(" -> missing"));
+    }
+}
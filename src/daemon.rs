@@ -0,0 +1,5576 @@
+//! The poll loop: one balance check per cycle and, when there is excess, one transfer.
+
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde_json::json;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::{Response, RpcResponseContext},
+};
+use solana_commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, CompiledInstruction, Instruction},
+    pubkey,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_system_interface::instruction as system_instruction;
+use solana_transaction_status_client_types::{
+    EncodedTransaction, UiMessage, UiTransactionEncoding,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::watch,
+    time::{sleep, timeout},
+};
+use tracing::{Span, error, field, info, info_span, warn};
+
+use crate::{
+    Config, DEFAULT_HISTORY_KEEP_FILES, DEFAULT_POLL_INTERVAL_DAYS,
+    DEFAULT_QUORUM_TOLERANCE_LAMPORTS, DEFAULT_SOL_THRESHOLD, DEFAULT_WEBHOOK_TIMEOUT_SECONDS,
+    Rounding, SECONDS_PER_DAY, amount,
+    approval::{self, Approval},
+    config_account::{self, OnChainConfig},
+    failover, history, lamports_to_sol,
+    metrics::Metrics,
+    notifier::{self, Notifier},
+    priority_fee::{self, DEFAULT_MAX_COMPUTE_UNIT_PRICE, PriorityFee},
+    redact,
+    rpc_deadline::{Deadline, DeadlineSender},
+    sol_to_lamports, stake,
+    state::{BalanceSample, MAX_BALANCE_SAMPLES, State},
+};
+
+/// SPL Memo program (v2).
+const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// Vote program; plain SOL sent to accounts it owns is almost always a mistake.
+const VOTE_PROGRAM_ID: Pubkey = pubkey!("Vote111111111111111111111111111111111111111");
+
+/// How a receiver with owner `owner` (`None`: no account yet) takes a plain system transfer,
+/// for the startup log. Off-curve addresses (program-derived, with no private key) are accepted
+/// like any other: a transfer to one that does not exist yet (an uninitialized PDA) creates it
+/// as a system account.
+fn receiver_kind(receiver: &Pubkey, owner: Option<Pubkey>) -> String {
+    let curve = if receiver.is_on_curve() {
+        "on-curve (wallet address)"
+    } else {
+        "off-curve (program-derived address, no private key)"
+    };
+    let account = match owner {
+        None => {
+            "no account yet; the first transfer creates it as a system account and must cover its rent-exempt minimum".to_string()
+        }
+        Some(owner) if owner == solana_system_interface::program::ID => "system-owned".to_string(),
+        Some(owner) => format!(
+            "owned by program {}; only that program can move SOL out of it",
+            owner
+        ),
+    };
+    format!("{}, {}", curve, account)
+}
+
+/// The transaction in `--build-unsigned` output (or the same output, signed): base64 after `#`
+/// comment lines.
+fn decode_transaction(text: &str) -> Result<Transaction> {
+    let encoded: String = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    Ok(bincode::deserialize(
+        &BASE64_STANDARD.decode(encoded).context("invalid base64")?,
+    )?)
+}
+
+/// Receiver, amount and (from the memo) sweep number of the system transfer from `sender` in
+/// `tx`, as built by `--build-unsigned`; `None` if it holds no such transfer.
+fn sweep_transfer(tx: &Transaction, sender: &Pubkey) -> Option<(Pubkey, u64, Option<u64>)> {
+    let keys = &tx.message.account_keys;
+    let program = |ix: &CompiledInstruction| keys.get(ix.program_id_index as usize);
+    let (receiver, amount) = tx.message.instructions.iter().find_map(|ix| {
+        // SystemInstruction::Transfer: u32 variant index 2, then the u64 lamports
+        let (variant, lamports) = ix.data.split_first_chunk::<4>()?;
+        let account = |i: usize| keys.get(*ix.accounts.get(i)? as usize);
+        if program(ix)? != &solana_system_interface::program::ID
+            || *variant != 2u32.to_le_bytes()
+            || account(0)? != sender
+        {
+            return None;
+        }
+        Some((*account(1)?, u64::from_le_bytes(lamports.try_into().ok()?)))
+    })?;
+    let sequence = tx.message.instructions.iter().find_map(|ix| {
+        if program(ix)? != &MEMO_PROGRAM_ID {
+            return None;
+        }
+        std::str::from_utf8(&ix.data)
+            .ok()?
+            .strip_prefix("automated-fund-transfer sweep #")?
+            .parse()
+            .ok()
+    });
+    Some((receiver, amount, sequence))
+}
+
+/// Memo text of keep-alive transactions.
+const KEEPALIVE_MEMO: &str = "automated-fund-transfer keep-alive";
+
+/// Memo attached to sweep number `sequence`.
+fn sweep_memo(sequence: u64) -> String {
+    format!("automated-fund-transfer sweep #{}", sequence)
+}
+
+/// Memo instruction signed by `signer`.
+fn memo_instruction(signer: &Pubkey, memo: &str) -> Instruction {
+    Instruction::new_with_bytes(
+        MEMO_PROGRAM_ID,
+        memo.as_bytes(),
+        vec![AccountMeta::new_readonly(*signer, true)],
+    )
+}
+
+/// Default largest share of a transfer its fee may be at the break-even poll interval
+/// (`fee_report_max_fee_fraction`).
+const DEFAULT_FEE_REPORT_MAX_FEE_FRACTION: f64 = 0.0001;
+
+/// Window `max_transfers_per_hour` counts transfers over.
+const TRANSFER_RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Default number of send attempts for one transfer when its blockhash keeps expiring
+/// (`max_send_attempts`).
+const DEFAULT_MAX_SEND_ATTEMPTS: u32 = 3;
+
+/// Blocks for which a blockhash stays valid after the block it names.
+const BLOCKHASH_VALIDITY_BLOCKS: u64 = 150;
+
+/// Fetches of a blockhash too close to expiry (`min_blockhash_validity_blocks`) before giving up.
+const MAX_BLOCKHASH_FETCHES: u32 = 3;
+
+/// Pause before refetching a blockhash too close to expiry.
+const BLOCKHASH_REFETCH_DELAY: Duration = Duration::from_millis(500);
+
+/// Pause between signature status checks while confirming within a blockhash validity window.
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Class of a failed `send_and_confirm_transaction`, used to decide whether to resend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SendFailure {
+    /// The blockhash was unknown or expired before the transaction landed. Safe to refetch the
+    /// blockhash, re-sign and resend: the expired transaction can no longer be processed.
+    BlockhashExpired,
+    /// The node's preflight simulation rejected the transaction, so it was never submitted and
+    /// can never land. Safe to retry next cycle, with the amount and fee worked out afresh.
+    Preflight,
+    /// The transaction was submitted and then failed, or its outcome is unknown (e.g. the
+    /// connection dropped while confirming). It may still land, so no other transfer may go out
+    /// until it has landed or expired.
+    Submission,
+}
+
+fn classify_send_error(err: &ClientError) -> SendFailure {
+    if matches!(
+        err.get_transaction_error(),
+        Some(TransactionError::BlockhashNotFound)
+    ) {
+        return SendFailure::BlockhashExpired;
+    }
+    // `send_and_confirm_transaction` reports an expired blockhash this way
+    if let ClientErrorKind::RpcError(RpcError::ForUser(msg)) = err.kind() {
+        if msg.starts_with("unable to confirm transaction") {
+            return SendFailure::BlockhashExpired;
+        }
+    }
+    if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(_),
+        ..
+    }) = err.kind()
+    {
+        return SendFailure::Preflight;
+    }
+    SendFailure::Submission
+}
+
+/// Run one RPC call inside a child span of the current poll cycle, timing it for `--profile`.
+macro_rules! traced_rpc {
+    ($cycle:expr, $method:literal, $call:expr) => {{
+        let _span = info_span!(parent: &$cycle.span, "rpc", method = $method).entered();
+        let started = Instant::now();
+        let result = $call;
+        $cycle.record_phase($method, started.elapsed());
+        result
+    }};
+}
+
+/// Oldest RPC node version serving every method the transfer path relies on
+/// (`getFeeForMessage` and `isBlockhashValid` arrived in 1.9).
+const MIN_RPC_VERSION: (u64, u64, u64) = (1, 9, 0);
+
+/// Oldest RPC node version serving `getRecentPrioritizationFees`, which
+/// `target_confirmation_slots` samples before each transfer.
+const MIN_PRIORITY_FEE_RPC_VERSION: (u64, u64, u64) = (1, 14, 0);
+
+/// Parse the `major.minor.patch` prefix of a `getVersion` `solana-core` string
+/// (e.g. "2.3.11 (src:...; feat:...)").
+fn parse_rpc_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split_whitespace().next()?.split('.').map(|p| {
+        p.chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>()
+            .parse::<u64>()
+            .ok()
+    });
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+/// Refuse an RPC node version too old for the enabled features (`priority_fee`:
+/// `target_confirmation_slots`). Unrecognized versions are only logged.
+fn check_rpc_version(solana_core: &str, priority_fee: bool) -> Result<()> {
+    let Some(node) = parse_rpc_version(solana_core) else {
+        warn!(
+            "Unrecognized RPC node version; skipping version check: solana_core = {}",
+            solana_core
+        );
+        return Ok(());
+    };
+    // One entry per enabled feature with its own minimum version
+    let mut required = vec![("transfers", MIN_RPC_VERSION)];
+    if priority_fee {
+        required.push(("target_confirmation_slots", MIN_PRIORITY_FEE_RPC_VERSION));
+    }
+    for (feature, min) in required {
+        if node < min {
+            return Err(anyhow!(
+                "RPC node version {} is too old for {} (requires {}.{}.{}); refusing to start",
+                solana_core,
+                feature,
+                min.0,
+                min.1,
+                min.2
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Genesis hashes of the public clusters, for `expected_cluster`.
+const KNOWN_CLUSTERS: &[(&str, &str)] = &[
+    (
+        "mainnet-beta",
+        "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d",
+    ),
+    ("testnet", "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+    ("devnet", "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+];
+
+/// Genesis hash of `expected_cluster`: a known cluster name or a genesis hash itself.
+fn parse_expected_cluster(value: &str) -> Result<Hash> {
+    let hash = KNOWN_CLUSTERS
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map_or(value, |(_, hash)| *hash);
+    hash.parse().map_err(|_| {
+        anyhow!(
+            "expected_cluster {:?} is neither a cluster name (mainnet-beta, testnet, devnet) nor a genesis hash",
+            value
+        )
+    })
+}
+
+/// Name of the public cluster with `genesis_hash`, or the hash itself.
+fn cluster_name(genesis_hash: &Hash) -> String {
+    let hash = genesis_hash.to_string();
+    KNOWN_CLUSTERS
+        .iter()
+        .find(|(_, known)| *known == hash)
+        .map_or(hash, |(name, _)| name.to_string())
+}
+
+/// Day names, Monday first, as accepted in `allowed_weekdays`.
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Index (Monday = 0) of a day name, full or abbreviated to three letters, in any case.
+fn parse_weekday(name: &str) -> Option<usize> {
+    WEEKDAYS.iter().position(|day| {
+        day.eq_ignore_ascii_case(name) || (name.len() == 3 && day[..3].eq_ignore_ascii_case(name))
+    })
+}
+
+/// Day of the week (Monday = 0) of `t` in UTC.
+fn weekday(t: SystemTime) -> usize {
+    let days = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / SECONDS_PER_DAY;
+    // 1970-01-01 was a Thursday
+    ((days + 3) % 7) as usize
+}
+
+/// Lines for a notification listing balance `samples` (oldest first), headed by a sparkline.
+fn balance_history(samples: &[BalanceSample]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = samples.iter().map(|s| s.lamports).min().unwrap_or(0);
+    let max = samples.iter().map(|s| s.lamports).max().unwrap_or(0);
+    let sparkline: String = samples
+        .iter()
+        .map(|s| {
+            let level = (s.lamports - min) as u128 * 7 / (max - min).max(1) as u128;
+            BARS[level as usize]
+        })
+        .collect();
+    let mut text = format!("Recent balances {}", sparkline);
+    for sample in samples {
+        text.push_str(&format!(
+            "\n{}  {} SOL",
+            humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(sample.unix_time)),
+            amount::sol(sample.lamports)
+        ));
+    }
+    text
+}
+
+/// Transfers of the `tiers` waterfall (levels increasing) for `balance`, highest tier first, as
+/// (floor, amount, receiver): each tier takes the balance above its level, or above `threshold`
+/// if that is higher, up to the next tier's level.
+fn tier_transfers(
+    tiers: &[(u64, Pubkey)],
+    threshold: u64,
+    balance: u64,
+) -> Vec<(u64, u64, Pubkey)> {
+    let mut ceiling = balance;
+    let mut transfers = Vec::new();
+    for (level, receiver) in tiers.iter().rev() {
+        let floor = (*level).max(threshold);
+        if ceiling > floor {
+            transfers.push((floor, ceiling - floor, *receiver));
+        }
+        ceiling = ceiling.min(*level);
+    }
+    transfers
+}
+
+/// Default shortest interval of adaptive polling.
+const DEFAULT_ADAPTIVE_POLL_MIN: Duration = Duration::from_secs(3600);
+
+/// Default `approval_timeout_seconds`.
+const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Default `failover_stale_seconds`, in poll intervals.
+const DEFAULT_FAILOVER_STALE_INTERVALS: u32 = 3;
+
+/// Default `obligation_horizon_days`.
+const DEFAULT_OBLIGATION_HORIZON_DAYS: u64 = 30;
+
+/// Bounds of adaptive polling (`adaptive_polling`).
+#[derive(Debug, Clone, Copy)]
+struct AdaptivePolling {
+    min: Duration,
+    max: Duration,
+}
+
+impl AdaptivePolling {
+    /// Interval until the next poll: the estimated time for the balance to accrue past `trigger`
+    /// at `rate` lamports per second, clamped to the bounds. Polls as late as allowed while the
+    /// rate is unknown and as soon as allowed once the trigger is already exceeded.
+    fn next_interval(&self, balance: u64, trigger: u64, rate: Option<f64>) -> Duration {
+        if balance > trigger {
+            return self.min;
+        }
+        match rate {
+            Some(rate) if rate > 0.0 => {
+                let eta = Duration::from_secs_f64((trigger - balance) as f64 / rate);
+                eta.clamp(self.min, self.max)
+            }
+            _ => self.max,
+        }
+    }
+}
+
+/// Sender balances pushed by a subscription (`geyser_endpoint`), which wake the poll loop as
+/// soon as the balance crosses the trigger instead of at the next poll.
+#[derive(Debug)]
+struct BalanceStream {
+    /// Latest streamed balance; `None` while the stream is down.
+    updates: watch::Receiver<Option<u64>>,
+    /// Streamed balance last looked at by `crossed`.
+    last: Option<u64>,
+    /// Trigger of the last cycle; nothing wakes the loop before the first.
+    trigger: Option<u64>,
+    /// Balance of the update that woke the loop, used by the cycle instead of `getBalance`.
+    woken_at: Option<u64>,
+}
+
+impl BalanceStream {
+    /// Wait for an update that takes the balance above the trigger from at or below it (or from
+    /// an unknown balance, after the stream came back). Never returns once the stream is gone.
+    async fn crossed(&mut self) -> u64 {
+        loop {
+            if self.updates.changed().await.is_err() {
+                return std::future::pending().await;
+            }
+            let balance = *self.updates.borrow_and_update();
+            let last = std::mem::replace(&mut self.last, balance);
+            let (Some(balance), Some(trigger)) = (balance, self.trigger) else {
+                continue;
+            };
+            if balance > trigger && last.is_none_or(|last| last <= trigger) {
+                self.woken_at = Some(balance);
+                return balance;
+            }
+        }
+    }
+}
+
+/// How long settings read from `config_account` (or its unavailability) are reused before the
+/// account is read again.
+const CONFIG_ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Attempts to fetch a transfer's receipt; the RPC node may not serve a just-confirmed
+/// transaction yet.
+const RECEIPT_ATTEMPTS: u32 = 3;
+
+/// Delay between receipt fetch attempts.
+const RECEIPT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Landed transaction details of a confirmed transfer, from `getTransaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Receipt {
+    slot: u64,
+    fee_lamports: u64,
+    /// Sender balance before and after the transaction, in lamports.
+    sender_balances: Option<(u64, u64)>,
+    /// Receiver balance before and after the transaction, in lamports.
+    receiver_balances: Option<(u64, u64)>,
+}
+
+/// What a poll cycle ended up doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CycleOutcome {
+    /// No excess above the threshold (and no keep-alive due).
+    Idle,
+    /// There was excess, but the transfer was deferred, skipped or failed this cycle.
+    Deferred,
+    /// A transfer was confirmed.
+    Transferred(Signature),
+    /// A keep-alive memo was confirmed.
+    KeepAlive(Signature),
+    /// Dry run: a transfer of this many lamports would have been sent.
+    WouldTransfer(u64),
+}
+
+/// Result of validating a receiver right before a transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReceiverCheck {
+    Valid,
+    /// The receiver must not be sent to; completes "receiver <pubkey> ...".
+    Refused(&'static str),
+    /// The receiver account could not be looked up.
+    Unknown,
+}
+
+/// Why `execute_transfer` gave up on a transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TransferFailure {
+    /// The cycle budget ran out before sending; nothing is wrong with the transfer itself.
+    BudgetExhausted,
+    /// An RPC call failed, identified by this error signature. Nothing can land.
+    Error(String),
+    /// The blockhash expired on each of `attempts` sends; `signature` is the last one's.
+    /// Nothing can land.
+    Expired {
+        error: String,
+        attempts: u32,
+        signature: Signature,
+    },
+    /// Rejected by the node's preflight simulation (`SendFailure::Preflight`); nothing was sent.
+    Preflight(String),
+    /// Failed after submission (`SendFailure::Submission`); the transaction may still land.
+    Submitted {
+        error: String,
+        signature: Signature,
+        blockhash: Hash,
+    },
+}
+
+/// A transfer whose transaction failed after submission and may still land.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InFlight {
+    signature: Signature,
+    blockhash: Hash,
+    receiver: Pubkey,
+    amount: u64,
+    sequence: u64,
+}
+
+/// Stable identity of a send failure for repeat detection: the transaction error when there is
+/// one (free of per-attempt details like blockhashes), else the client error text.
+fn failure_signature(err: &ClientError) -> String {
+    match err.get_transaction_error() {
+        Some(tx_err) => format!("{:?}", tx_err),
+        None => err.to_string(),
+    }
+}
+
+/// A poll cycle in progress: its tracing span, its start time and deadline (`cycle_budget`) and
+/// the timings of its RPC calls.
+struct Cycle {
+    span: Span,
+    started: Instant,
+    deadline: Option<Instant>,
+    /// Wall-clock duration of each RPC call made so far, in call order.
+    phases: Mutex<Vec<(&'static str, Duration)>>,
+}
+
+impl Cycle {
+    fn new(span: Span) -> Self {
+        Self {
+            span,
+            started: Instant::now(),
+            deadline: None,
+            phases: Mutex::default(),
+        }
+    }
+
+    /// The cycle, due to end `budget` after it started.
+    fn with_budget(mut self, budget: Option<Duration>) -> Self {
+        self.deadline = budget.map(|b| self.started + b);
+        self
+    }
+
+    /// Time left until the deadline (zero once it passed); `None` without a budget.
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether the budget ran out, logged as abandoning the cycle before `phase`.
+    fn out_of_budget(&self, phase: &str) -> bool {
+        if self.remaining().is_some_and(|r| r.is_zero()) {
+            warn!(
+                "Cycle budget exhausted before {}; abandoning cycle: elapsed_s = {}",
+                phase,
+                self.started.elapsed().as_secs()
+            );
+            return true;
+        }
+        false
+    }
+
+    fn record_phase(&self, method: &'static str, elapsed: Duration) {
+        self.phases.lock().unwrap().push((method, elapsed));
+    }
+}
+
+/// Everything the poll loop needs, built once from the config at startup.
+pub struct Daemon {
+    rpc: RpcClient,
+    /// Endpoint polled for signature statuses instead of `rpc` (`confirm_rpc_provider`).
+    confirm_rpc: Option<RpcClient>,
+    /// Genesis hash `rpc_provider` must report (`expected_cluster`).
+    expected_genesis_hash: Option<Hash>,
+    /// The sender keypair; `None` when it is kept offline (`--build-unsigned`, `--broadcast`).
+    keypair: Option<Keypair>,
+    /// Separate fee payer; the sender pays its own fees when `None`.
+    fee_payer: Option<Keypair>,
+    sender: Pubkey,
+    receiver: Pubkey,
+    receiver_allowlist: Vec<Pubkey>,
+    /// Receiver swept to instead when the chosen receiver fails validation at transfer time.
+    fallback_receiver: Option<Pubkey>,
+    /// Transfer even if the receiver is a vote account.
+    allow_vote_account_receiver: bool,
+    /// `amount_bands` as (minimum excess in lamports, receiver), by increasing minimum.
+    amount_bands: Vec<(u64, Pubkey)>,
+    /// `tiers` as (sender balance level in lamports, receiver), by increasing level.
+    tiers: Vec<(u64, Pubkey)>,
+    /// `receiver_windows` as (start, end, receiver), by start; they do not overlap.
+    receiver_windows: Vec<(SystemTime, SystemTime, Pubkey)>,
+    threshold_lamports: u64,
+    /// Balance above which a transfer is triggered (the threshold plus `trigger_relative_percent`).
+    trigger_lamports: u64,
+    trigger_relative_percent: f64,
+    /// On-chain account overriding the threshold and poll interval.
+    config_account: Option<Pubkey>,
+    /// Last read of `config_account` (`None` if it was unavailable) and when it was made.
+    on_chain_config: Option<(Option<OnChainConfig>, Instant)>,
+    near_threshold_margin_lamports: Option<u64>,
+    monitored_stake_accounts: Vec<Pubkey>,
+    deactivating_stake_min_lamports: u64,
+    /// Amount the threshold is raised by while significant monitored stake is deactivating.
+    deactivation_reserve_lamports: u64,
+    /// `scheduled_obligations` not yet due, as (due time, lamports), by config order.
+    obligations: Vec<(SystemTime, u64)>,
+    /// How long before its due time an obligation is retained.
+    obligation_horizon: Duration,
+    /// Keep the sender's rent-exempt minimum on top of the threshold.
+    reserve_rent_exempt: bool,
+    /// The sender's rent-exempt minimum, read once when first needed.
+    rent_exempt_minimum: Option<u64>,
+    receiver_target_lamports: Option<u64>,
+    receiver_max_expected_lamports: Option<u64>,
+    /// Cap on the first transfer after startup; cleared once that transfer confirms.
+    first_transfer_max_lamports: Option<u64>,
+    /// Largest single transfer; larger ones are sent in chunks of this size.
+    chunk_lamports: Option<u64>,
+    /// Manual approval gate for large transfers.
+    approval: Option<Approval>,
+    poll_interval: Duration,
+    adaptive_polling: Option<AdaptivePolling>,
+    /// Interval before the next poll; the regular poll interval unless adaptive polling is on.
+    next_poll_interval: Duration,
+    /// Shorten the sleep after an overrunning cycle by the overrun.
+    catch_up_overruns: bool,
+    /// Last observed sender balance and when it was read, for the accrual rate estimate.
+    last_balance: Option<(u64, Instant)>,
+    /// Estimated sender accrual rate in lamports per second, from the last balance increase.
+    accrual_rate: Option<f64>,
+    /// Recent balance readings, oldest first (at most `MAX_BALANCE_SAMPLES`).
+    recent_balances: Vec<BalanceSample>,
+    /// How many of `recent_balances` transfer notifications list.
+    notification_balance_history: usize,
+    cycle_budget: Option<Duration>,
+    /// Deadline of the cycle in progress, which every RPC request of `rpc`, `confirm_rpc` and
+    /// `quorum_rpcs` is cut off at.
+    rpc_deadline: Deadline,
+    /// Commitment of the per-cycle balance read.
+    balance_commitment: CommitmentConfig,
+    /// Re-read the balance at finalized commitment before transferring.
+    finalized_recheck: bool,
+    /// Sign with a finalized blockhash and confirm until its last valid block height passes.
+    finalized_blockhash: bool,
+    /// Blocks of validity a fetched blockhash must have left to be signed with.
+    min_blockhash_validity: Option<u64>,
+    /// Send attempts of one transfer within a cycle before an expiring one is given up.
+    max_send_attempts: u32,
+    /// Priority fee aimed at `target_confirmation_slots`; transfers carry no compute budget
+    /// instructions when `None`.
+    priority_fee: Option<PriorityFee>,
+    /// Consecutive identical transfer failures after which a critical alert is sent.
+    repeated_failure_limit: Option<u32>,
+    /// Suspend transfers (until restart) once `repeated_failure_limit` is reached.
+    halt_on_repeated_failure: bool,
+    /// Signature of the last transfer failure and how many times in a row it occurred.
+    failure_streak: Option<(String, u32)>,
+    /// Transfers suspended after repeated identical failures or too many transfers in an hour.
+    halted: bool,
+    /// Transfers in any one hour above which an alert is sent (`max_transfers_per_hour`).
+    max_transfers_per_hour: Option<usize>,
+    /// Suspend transfers (until restart) once `max_transfers_per_hour` is exceeded.
+    halt_on_excessive_transfers: bool,
+    /// Confirmation times of the transfers of the last hour.
+    recent_transfers: VecDeque<Instant>,
+    /// The `max_transfers_per_hour` alert went out and the rate has not dropped back since.
+    transfer_rate_alerted: bool,
+    /// Gap since the last sweep, in average intervals between sweeps, that triggers an alert.
+    dry_spell_alert_multiple: Option<f64>,
+    /// The dry spell alert went out and no sweep has confirmed since.
+    dry_spell_alerted: bool,
+    /// A transfer that may still land; no other is sent until it has landed or expired.
+    in_flight: Option<InFlight>,
+    /// Extra endpoints (URL and client) that re-read the balance before a transfer.
+    quorum_rpcs: Vec<(String, RpcClient)>,
+    /// Reads (`rpc` included) that must agree on the balance before a transfer.
+    quorum_balance_reads: usize,
+    quorum_tolerance_lamports: u64,
+    /// Balance read by this cycle until the quorum has confirmed it, which happens once per
+    /// cycle, before its first transfer.
+    unconfirmed_balance: Option<u64>,
+    min_uptime: Duration,
+    pause_file: Option<PathBuf>,
+    /// Heartbeat lock of a warm-standby pair; only its holder transfers.
+    failover: Option<failover::Lock>,
+    /// Whether this instance held the failover lock at the last refresh (`None` before the first).
+    failover_active: Option<bool>,
+    pause_when_delinquent: bool,
+    /// Days (Monday = 0) on which transfers may be made; any day when empty.
+    allowed_weekdays: Vec<usize>,
+    /// Alert when the balance reads zero after a nonzero reading.
+    alert_on_zero_balance: bool,
+    /// Last nonzero sender balance; cleared once a drop to zero has been alerted.
+    last_nonzero_balance: Option<u64>,
+    sweep_per_epoch: bool,
+    sweep_epoch_slot_offset: u64,
+    sweep_delay: Option<Duration>,
+    /// When the current, not yet swept excess was first seen (`sweep_delay`).
+    excess_since: Option<Instant>,
+    keepalive_interval: Option<Duration>,
+    history_file: Option<PathBuf>,
+    /// Decide and log transfers without sending anything.
+    dry_run: bool,
+    /// In a dry run, also send each would-be transfer and keep-alive as a "[DRY RUN]"
+    /// notification (`--dry-run-notify`).
+    dry_run_notify: bool,
+    /// Log the duration of every RPC call and of the whole cycle (`--profile`).
+    profile: bool,
+    history_rotation: Option<history::Rotation>,
+    audit_sign: bool,
+    state_path: Option<PathBuf>,
+    state: State,
+    notifier: Notifier,
+    metrics: Metrics,
+    started_at: Instant,
+    /// Time of the last on-chain activity (transfer or keep-alive); used by keep-alive mode.
+    last_transfer_at: Instant,
+    /// How often the fee projection is logged (`fee_report_interval_seconds`).
+    fee_report_interval: Option<Duration>,
+    fee_report_max_fee_fraction: f64,
+    last_fee_report: Option<Instant>,
+    /// Where `--build-unsigned` writes the unsigned transfer ("-" for stdout).
+    unsigned_output: Option<String>,
+    /// Balance monitor only (`observe_only`): metrics are exported, nothing is ever sent.
+    observe_only: bool,
+    /// Balance updates pushed by `geyser_endpoint`, if subscribed.
+    balance_stream: Option<BalanceStream>,
+}
+
+impl Daemon {
+    /// Read the keypair and state file, validate the receiver and set up the RPC client. With
+    /// `observe_only` no keypair is read and `watch_pubkey` is the account watched.
+    pub fn new(cfg: &Config, notifier: Notifier, metrics: Metrics) -> Result<Self> {
+        Self::build(cfg, notifier, metrics, true)
+    }
+
+    /// Like `new`, for a host without the sender keypair (`--build-unsigned`, `--broadcast`):
+    /// the sender is `sender_pubkey` and nothing is signed.
+    pub fn new_offline(cfg: &Config, notifier: Notifier, metrics: Metrics) -> Result<Self> {
+        Self::build(cfg, notifier, metrics, false)
+    }
+
+    fn build(cfg: &Config, notifier: Notifier, metrics: Metrics, signing: bool) -> Result<Self> {
+        let observe_only = cfg.observe_only.unwrap_or(false);
+        let without_keypair = if observe_only {
+            "with observe_only"
+        } else {
+            "while the sender keypair is kept offline"
+        };
+        // Read keypair
+        let (keypair, sender, fee_payer) = if signing && !observe_only {
+            let keypair = cfg.load_sender_keypair()?;
+            let sender = keypair.pubkey();
+            info!("Loaded sender keypair: {}", redact::addr(sender));
+            let fee_payer = cfg.load_fee_payer_keypair()?;
+            if let Some(fee_payer) = &fee_payer {
+                info!(
+                    "Loaded fee payer keypair: {}",
+                    redact::addr(fee_payer.pubkey())
+                );
+            }
+            (Some(keypair), sender, fee_payer)
+        } else {
+            let (key, pubkey) = if observe_only {
+                ("watch_pubkey", &cfg.watch_pubkey)
+            } else {
+                ("sender_pubkey", &cfg.sender_pubkey)
+            };
+            let sender: Pubkey = pubkey
+                .as_deref()
+                .ok_or_else(|| anyhow!("{} is required {}", key, without_keypair))?
+                .parse()
+                .with_context(|| format!("parsing {}", key))?;
+            if cfg.fee_payer_keypair.is_some() || cfg.vault_fee_payer_keypair.is_some() {
+                return Err(anyhow!(
+                    "fee_payer_keypair cannot be used {}; the sender pays the fee",
+                    without_keypair
+                ));
+            }
+            if observe_only {
+                info!(
+                    "Observe only: balance and threshold metrics are exported, transfers are disabled: watch_pubkey = {}",
+                    redact::addr(sender)
+                );
+            } else {
+                info!(
+                    "Sender keypair kept offline; transactions are not signed here: sender = {}",
+                    redact::addr(sender)
+                );
+            }
+            (None, sender, None)
+        };
+
+        let state_path = cfg.state_file.as_ref().map(PathBuf::from);
+        let mut state = match &state_path {
+            Some(path) => State::load(path).context(
+                "fix or remove the state file; the sweep sequence is then rebuilt from history_file",
+            )?,
+            None => State::default(),
+        };
+        // The history is the record of every sweep; a lost or restored state file must not
+        // reuse sequence numbers
+        if let Some(path) = &cfg.history_file {
+            match history::last_sequence(Path::new(path)) {
+                Ok(Some(last)) if last > state.transfer_sequence => {
+                    warn!(
+                        "Sweep sequence is behind the history file; continuing from the history: state_sequence = {}, history_sequence = {}",
+                        state.transfer_sequence, last
+                    );
+                    state.transfer_sequence = last;
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "Failed to read the sweep sequence from the history file: {:#}",
+                    e
+                ),
+            }
+        }
+
+        // Resume the accrual estimate where the last run left it
+        let last_balance = state.balance_samples.last().and_then(|sample| {
+            let age = SystemTime::now()
+                .duration_since(UNIX_EPOCH + Duration::from_secs(sample.unix_time))
+                .unwrap_or_default();
+            Instant::now()
+                .checked_sub(age)
+                .map(|at| (sample.lamports, at))
+        });
+        let accrual_rate = state.accrual_rate();
+        let recent_balances = state.balance_samples.clone();
+        let notification_balance_history = cfg.notification_balance_history.unwrap_or(0);
+        if notification_balance_history > MAX_BALANCE_SAMPLES {
+            return Err(anyhow!(
+                "notification_balance_history must be at most {}, got {}",
+                MAX_BALANCE_SAMPLES,
+                notification_balance_history
+            ));
+        }
+
+        // Parse receiver pubkey; a receiver confirmed with `migrate-receiver` takes precedence.
+        // An observer without one never sends anywhere; the watched account stands in for it
+        let receiver: Pubkey = if observe_only && cfg.receiver_pubkey.is_empty() {
+            sender
+        } else {
+            cfg.receiver_pubkey
+                .parse()
+                .context("parsing receiver pubkey")?
+        };
+        let receiver = match &state.receiver_override {
+            Some(r) => {
+                let r: Pubkey = r
+                    .parse()
+                    .context("parsing receiver override in state file")?;
+                info!(
+                    "Using receiver override from state file: receiver = {}, config_receiver = {}",
+                    redact::addr(r),
+                    redact::addr(receiver)
+                );
+                r
+            }
+            None => receiver,
+        };
+
+        // Only ever send to vetted addresses
+        let receiver_allowlist = cfg
+            .receiver_allowlist
+            .iter()
+            .map(|s| {
+                s.parse::<Pubkey>()
+                    .with_context(|| format!("parsing receiver_allowlist entry {}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !receiver_allowlist.is_empty() && !receiver_allowlist.contains(&receiver) {
+            return Err(anyhow!(
+                "receiver {} is not in receiver_allowlist; refusing to start",
+                redact::addr(receiver)
+            ));
+        }
+
+        // Size-based routing; bands run from their minimum up to the next band's
+        let amount_rounding = cfg.amount_rounding.unwrap_or(Rounding::Floor);
+        let mut amount_bands: Vec<(u64, Pubkey)> = Vec::new();
+        for band in &cfg.amount_bands {
+            if !band.min_sol.is_finite() || band.min_sol < 0.0 {
+                return Err(anyhow!(
+                    "amount_bands min_sol must be a non-negative number, got {}",
+                    band.min_sol
+                ));
+            }
+            let min = sol_to_lamports(band.min_sol, amount_rounding);
+            if amount_bands.last().is_some_and(|(prev, _)| *prev >= min) {
+                return Err(anyhow!(
+                    "amount_bands must be listed by strictly increasing min_sol (overlapping or unordered band at min_sol = {})",
+                    band.min_sol
+                ));
+            }
+            let band_receiver: Pubkey = band
+                .receiver
+                .parse()
+                .with_context(|| format!("parsing amount_bands receiver {}", band.receiver))?;
+            if !receiver_allowlist.is_empty() && !receiver_allowlist.contains(&band_receiver) {
+                return Err(anyhow!(
+                    "amount_bands receiver {} is not in receiver_allowlist; refusing to start",
+                    redact::addr(band_receiver)
+                ));
+            }
+            amount_bands.push((min, band_receiver));
+        }
+
+        let mut tiers: Vec<(u64, Pubkey)> = Vec::new();
+        for tier in &cfg.tiers {
+            if !(tier.above_sol.is_finite() && tier.above_sol >= 0.0) {
+                return Err(anyhow!(
+                    "tiers above_sol must be a non-negative number, got {}",
+                    tier.above_sol
+                ));
+            }
+            let level = sol_to_lamports(tier.above_sol, amount_rounding);
+            if tiers.last().is_some_and(|(prev, _)| *prev >= level) {
+                return Err(anyhow!(
+                    "tiers must be listed by strictly increasing above_sol (duplicate or unordered tier at above_sol = {})",
+                    tier.above_sol
+                ));
+            }
+            let tier_receiver: Pubkey = tier
+                .receiver
+                .parse()
+                .with_context(|| format!("parsing tiers receiver {}", tier.receiver))?;
+            if !receiver_allowlist.is_empty() && !receiver_allowlist.contains(&tier_receiver) {
+                return Err(anyhow!(
+                    "tiers receiver {} is not in receiver_allowlist; refusing to start",
+                    redact::addr(tier_receiver)
+                ));
+            }
+            tiers.push((level, tier_receiver));
+        }
+        if !tiers.is_empty() && !amount_bands.is_empty() {
+            return Err(anyhow!("tiers and amount_bands cannot be combined"));
+        }
+
+        let mut receiver_windows: Vec<(SystemTime, SystemTime, Pubkey)> = Vec::new();
+        for window in &cfg.receiver_windows {
+            let from = history::parse_date(&window.from_date).with_context(|| {
+                format!("parsing receiver_windows from_date {:?}", window.from_date)
+            })?;
+            let to = history::parse_date(&window.to_date).with_context(|| {
+                format!("parsing receiver_windows to_date {:?}", window.to_date)
+            })?;
+            if from >= to {
+                return Err(anyhow!(
+                    "receiver_windows from_date {} must be before to_date {}",
+                    window.from_date,
+                    window.to_date
+                ));
+            }
+            let window_receiver: Pubkey = window.receiver.parse().with_context(|| {
+                format!("parsing receiver_windows receiver {}", window.receiver)
+            })?;
+            if !receiver_allowlist.is_empty() && !receiver_allowlist.contains(&window_receiver) {
+                return Err(anyhow!(
+                    "receiver_windows receiver {} is not in receiver_allowlist; refusing to start",
+                    redact::addr(window_receiver)
+                ));
+            }
+            receiver_windows.push((from, to, window_receiver));
+        }
+        receiver_windows.sort_by_key(|(from, _, _)| *from);
+        if let Some(pair) = receiver_windows
+            .windows(2)
+            .find(|pair| pair[1].0 < pair[0].1)
+        {
+            return Err(anyhow!(
+                "receiver_windows overlap: a window starting {} begins before the one starting {} ends",
+                humantime::format_rfc3339_seconds(pair[1].0),
+                humantime::format_rfc3339_seconds(pair[0].0)
+            ));
+        }
+
+        let fallback_receiver = cfg
+            .fallback_receiver_pubkey
+            .as_ref()
+            .map(|s| {
+                s.parse::<Pubkey>()
+                    .with_context(|| format!("parsing fallback_receiver_pubkey {}", s))
+            })
+            .transpose()?;
+        if let Some(fallback) = fallback_receiver {
+            if !receiver_allowlist.is_empty() && !receiver_allowlist.contains(&fallback) {
+                return Err(anyhow!(
+                    "fallback_receiver_pubkey {} is not in receiver_allowlist; refusing to start",
+                    redact::addr(fallback)
+                ));
+            }
+        }
+
+        // Setup RPC client
+        let commitment = CommitmentConfig::finalized();
+        let balance_commitment = CommitmentConfig {
+            commitment: cfg.balance_commitment.unwrap_or(CommitmentLevel::Finalized),
+        };
+        let cycle_budget = cfg.cycle_budget_seconds.map(Duration::from_secs);
+        let rpc_deadline = Deadline::default();
+        let new_rpc = |url: &str| match cycle_budget {
+            Some(budget) => RpcClient::new_sender(
+                DeadlineSender::new(
+                    HttpSender::new_with_timeout(url, budget),
+                    rpc_deadline.clone(),
+                ),
+                RpcClientConfig::with_commitment(commitment),
+            ),
+            None => RpcClient::new_with_commitment(url.to_string(), commitment),
+        };
+        let rpc = new_rpc(&cfg.rpc_provider);
+        let confirm_rpc = cfg.confirm_rpc_provider.as_deref().map(new_rpc);
+
+        let quorum_balance_reads = cfg.quorum_balance_reads.unwrap_or(1);
+        if quorum_balance_reads > 1 + cfg.quorum_rpc_providers.len() {
+            return Err(anyhow!(
+                "quorum_balance_reads = {} needs at least {} quorum_rpc_providers, {} configured",
+                quorum_balance_reads,
+                quorum_balance_reads - 1,
+                cfg.quorum_rpc_providers.len()
+            ));
+        }
+        let quorum_rpcs = if quorum_balance_reads > 1 {
+            cfg.quorum_rpc_providers
+                .iter()
+                .map(|url| (url.clone(), new_rpc(url)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let expected_genesis_hash = cfg
+            .expected_cluster
+            .as_deref()
+            .map(parse_expected_cluster)
+            .transpose()?;
+
+        let threshold_rounding = cfg.threshold_rounding.unwrap_or(Rounding::Floor);
+
+        let mut obligations = Vec::new();
+        for obligation in &cfg.scheduled_obligations {
+            if !(obligation.amount_sol.is_finite() && obligation.amount_sol > 0.0) {
+                return Err(anyhow!(
+                    "scheduled_obligations amount_sol must be a positive number, got {}",
+                    obligation.amount_sol
+                ));
+            }
+            let due = history::parse_date(&obligation.date).with_context(|| {
+                format!("parsing scheduled_obligations date {:?}", obligation.date)
+            })?;
+            obligations.push((
+                due,
+                sol_to_lamports(obligation.amount_sol, threshold_rounding),
+            ));
+        }
+
+        let threshold_lamports = match (cfg.sol_threshold_lamports, cfg.sol_threshold) {
+            (Some(lamports), sol) => {
+                if let Some(sol) = sol {
+                    warn!(
+                        "Both sol_threshold and sol_threshold_lamports are set; using sol_threshold_lamports: sol_threshold = {}, sol_threshold_lamports = {}",
+                        sol, lamports
+                    );
+                }
+                lamports
+            }
+            (None, sol) => {
+                let sol = sol.unwrap_or(DEFAULT_SOL_THRESHOLD);
+                let lamports = sol_to_lamports(sol, threshold_rounding);
+                info!(
+                    "Derived lamport threshold: sol_threshold = {}, threshold_lamports = {}, threshold_rounding = {:?}; set sol_threshold_lamports to pin it exactly",
+                    sol, lamports, threshold_rounding
+                );
+                lamports
+            }
+        };
+        let threshold_sol = lamports_to_sol(threshold_lamports);
+        let trigger_relative_percent = cfg.trigger_relative_percent.unwrap_or(0.0);
+        if trigger_relative_percent < 0.0 {
+            return Err(anyhow!(
+                "trigger_relative_percent must not be negative, got {}",
+                trigger_relative_percent
+            ));
+        }
+        let trigger_lamports = threshold_lamports
+            + sol_to_lamports(
+                threshold_sol * trigger_relative_percent / 100.0,
+                threshold_rounding,
+            );
+        let config_account = cfg
+            .config_account
+            .as_deref()
+            .map(|s| s.parse::<Pubkey>().context("parsing config_account"))
+            .transpose()?;
+        let near_threshold_margin_lamports = cfg
+            .near_threshold_margin_sol
+            .map(|sol| sol_to_lamports(sol, threshold_rounding));
+        let receiver_target_lamports = cfg
+            .receiver_target_sol
+            .map(|sol| sol_to_lamports(sol, amount_rounding));
+        let monitored_stake_accounts = cfg
+            .monitored_stake_accounts
+            .iter()
+            .map(|s| {
+                s.parse::<Pubkey>()
+                    .with_context(|| format!("parsing monitored_stake_accounts entry {}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !monitored_stake_accounts.is_empty() && cfg.deactivation_reserve_sol.is_none() {
+            return Err(anyhow!(
+                "monitored_stake_accounts requires deactivation_reserve_sol"
+            ));
+        }
+        let receiver_max_expected_lamports = cfg
+            .receiver_max_expected_sol
+            .map(|sol| sol_to_lamports(sol, amount_rounding));
+        let first_transfer_max_lamports = cfg
+            .first_transfer_max_sol
+            .map(|sol| sol_to_lamports(sol, amount_rounding));
+        let chunk_lamports = cfg
+            .chunk_size_sol
+            .map(|sol| sol_to_lamports(sol, amount_rounding));
+        if chunk_lamports == Some(0) {
+            return Err(anyhow!(
+                "chunk_size_sol must be at least one lamport, got {}",
+                cfg.chunk_size_sol.unwrap_or_default()
+            ));
+        }
+        let approval = match cfg.approval_required_above_sol {
+            Some(sol) => {
+                let source = match (&cfg.approval_file, &cfg.approval_url) {
+                    (Some(path), None) => approval::Source::File(PathBuf::from(path)),
+                    (None, Some(url)) => approval::Source::Url(
+                        url.parse()
+                            .with_context(|| format!("parsing approval_url {}", url))?,
+                    ),
+                    _ => {
+                        return Err(anyhow!(
+                            "approval_required_above_sol requires exactly one of approval_file and approval_url"
+                        ));
+                    }
+                };
+                let timeout = cfg
+                    .approval_timeout_seconds
+                    .map_or(DEFAULT_APPROVAL_TIMEOUT, Duration::from_secs);
+                if let Some(budget) = cycle_budget {
+                    if timeout >= budget {
+                        return Err(anyhow!(
+                            "approval_timeout_seconds ({}) must be shorter than cycle_budget_seconds ({})",
+                            timeout.as_secs(),
+                            budget.as_secs()
+                        ));
+                    }
+                }
+                if !notifier.enabled() {
+                    warn!(
+                        "approval_required_above_sol is set but notifications are disabled; approval requests will only be logged"
+                    );
+                }
+                let request_timeout = Duration::from_secs(
+                    cfg.webhook_timeout_seconds
+                        .unwrap_or(DEFAULT_WEBHOOK_TIMEOUT_SECONDS),
+                );
+                Some(Approval::new(
+                    sol_to_lamports(sol, amount_rounding),
+                    source,
+                    timeout,
+                    request_timeout,
+                )?)
+            }
+            None => {
+                if cfg.approval_file.is_some() || cfg.approval_url.is_some() {
+                    warn!(
+                        "approval_file and approval_url are ignored without approval_required_above_sol"
+                    );
+                }
+                None
+            }
+        };
+        let poll_interval = match &cfg.poll_interval {
+            Some(text) => {
+                if let Some(days) = cfg.poll_interval_days {
+                    warn!(
+                        "Both poll_interval_days and poll_interval are set; using poll_interval: poll_interval_days = {}, poll_interval = {}",
+                        days, text
+                    );
+                }
+                let interval = humantime::parse_duration(text)
+                    .with_context(|| format!("parsing poll_interval {:?}", text))?;
+                if interval.is_zero() {
+                    return Err(anyhow!("poll_interval must be longer than zero"));
+                }
+                interval
+            }
+            None => Duration::from_secs(
+                cfg.poll_interval_days.unwrap_or(DEFAULT_POLL_INTERVAL_DAYS) * SECONDS_PER_DAY,
+            ),
+        };
+
+        let adaptive_polling = cfg.adaptive_polling.unwrap_or(false).then(|| {
+            let min = cfg
+                .adaptive_poll_min_seconds
+                .map_or(DEFAULT_ADAPTIVE_POLL_MIN, Duration::from_secs);
+            let max = cfg
+                .adaptive_poll_max_seconds
+                .map_or(poll_interval, Duration::from_secs);
+            AdaptivePolling { min, max }
+        });
+        if let Some(bounds) = adaptive_polling {
+            if bounds.min > bounds.max {
+                return Err(anyhow!(
+                    "adaptive_poll_min_seconds ({}) exceeds adaptive_poll_max_seconds ({})",
+                    bounds.min.as_secs(),
+                    bounds.max.as_secs()
+                ));
+            }
+        }
+
+        let failover = match (cfg.role, &cfg.failover_lock_file) {
+            (Some(role), Some(path)) => {
+                let longest = adaptive_polling.map_or(poll_interval, |b| b.max.max(poll_interval));
+                let stale = cfg.failover_stale_seconds.map_or(
+                    poll_interval * DEFAULT_FAILOVER_STALE_INTERVALS,
+                    Duration::from_secs,
+                );
+                if stale <= longest {
+                    return Err(anyhow!(
+                        "failover_stale_seconds ({}) must be longer than the poll interval ({}s)",
+                        stale.as_secs(),
+                        longest.as_secs()
+                    ));
+                }
+                let holder = cfg.deployment_name.clone().unwrap_or_default();
+                info!(
+                    "Warm-standby failover enabled: role = {:?}, lock_file = {}, holder = {}, stale_s = {}",
+                    role,
+                    path,
+                    holder,
+                    stale.as_secs()
+                );
+                Some(failover::Lock::new(
+                    PathBuf::from(path),
+                    holder,
+                    role,
+                    stale,
+                ))
+            }
+            (None, None) => None,
+            _ => return Err(anyhow!("role and failover_lock_file must be set together")),
+        };
+
+        let allowed_weekdays = match &cfg.allowed_weekdays {
+            Some(names) if names.is_empty() => {
+                return Err(anyhow!(
+                    "allowed_weekdays must list at least one day (leave it unset to allow any day)"
+                ));
+            }
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    parse_weekday(name).ok_or_else(|| {
+                        anyhow!(
+                            "allowed_weekdays entry {:?} is not a day name (e.g. \"Mon\" or \"Monday\")",
+                            name
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        let min_blockhash_validity = cfg.min_blockhash_validity_blocks;
+        if min_blockhash_validity.is_some_and(|min| min >= BLOCKHASH_VALIDITY_BLOCKS) {
+            return Err(anyhow!(
+                "min_blockhash_validity_blocks must be below {}, got {}",
+                BLOCKHASH_VALIDITY_BLOCKS,
+                min_blockhash_validity.unwrap_or_default()
+            ));
+        }
+
+        let fee_report_interval = cfg.fee_report_interval_seconds.map(Duration::from_secs);
+        if fee_report_interval.is_some() && cfg.history_file.is_none() {
+            return Err(anyhow!("fee_report_interval_seconds requires history_file"));
+        }
+        let fee_report_max_fee_fraction = cfg
+            .fee_report_max_fee_fraction
+            .unwrap_or(DEFAULT_FEE_REPORT_MAX_FEE_FRACTION);
+        if !(fee_report_max_fee_fraction > 0.0 && fee_report_max_fee_fraction <= 1.0) {
+            return Err(anyhow!(
+                "fee_report_max_fee_fraction must be above 0 and at most 1, got {}",
+                fee_report_max_fee_fraction
+            ));
+        }
+
+        let max_send_attempts = cfg.max_send_attempts.unwrap_or(DEFAULT_MAX_SEND_ATTEMPTS);
+        if max_send_attempts == 0 {
+            return Err(anyhow!("max_send_attempts must be at least 1"));
+        }
+        let priority_fee = match cfg.target_confirmation_slots {
+            Some(0) => return Err(anyhow!("target_confirmation_slots must be at least 1")),
+            Some(target) => Some(PriorityFee::new(
+                target,
+                cfg.max_compute_unit_price
+                    .unwrap_or(DEFAULT_MAX_COMPUTE_UNIT_PRICE),
+            )),
+            None => None,
+        };
+
+        let sweep_per_epoch = cfg.sweep_per_epoch.unwrap_or(false);
+        if sweep_per_epoch && state_path.is_none() {
+            warn!(
+                "sweep_per_epoch without state_file: the last swept epoch is forgotten on restart"
+            );
+        }
+
+        if let Some(multiple) = cfg.dry_spell_alert_multiple {
+            if multiple.is_nan() || multiple < 1.0 {
+                return Err(anyhow!(
+                    "dry_spell_alert_multiple must be at least 1, got {}",
+                    multiple
+                ));
+            }
+        }
+
+        let started_at = Instant::now();
+        Ok(Self {
+            rpc,
+            confirm_rpc,
+            keypair,
+            fee_payer,
+            sender,
+            receiver,
+            receiver_allowlist,
+            fallback_receiver,
+            allow_vote_account_receiver: cfg.allow_vote_account_receiver.unwrap_or(false),
+            amount_bands,
+            tiers,
+            receiver_windows,
+            threshold_lamports,
+            trigger_lamports,
+            trigger_relative_percent,
+            config_account,
+            on_chain_config: None,
+            near_threshold_margin_lamports,
+            monitored_stake_accounts,
+            deactivating_stake_min_lamports: sol_to_lamports(
+                cfg.deactivating_stake_min_sol.unwrap_or(0.0),
+                threshold_rounding,
+            ),
+            deactivation_reserve_lamports: sol_to_lamports(
+                cfg.deactivation_reserve_sol.unwrap_or(0.0),
+                threshold_rounding,
+            ),
+            obligations,
+            obligation_horizon: Duration::from_secs(
+                cfg.obligation_horizon_days
+                    .unwrap_or(DEFAULT_OBLIGATION_HORIZON_DAYS)
+                    * SECONDS_PER_DAY,
+            ),
+            reserve_rent_exempt: cfg.reserve_rent_exempt.unwrap_or(true),
+            rent_exempt_minimum: None,
+            receiver_target_lamports,
+            receiver_max_expected_lamports,
+            first_transfer_max_lamports,
+            chunk_lamports,
+            approval,
+            poll_interval,
+            adaptive_polling,
+            next_poll_interval: poll_interval,
+            catch_up_overruns: cfg.catch_up_overruns.unwrap_or(false),
+            last_balance,
+            accrual_rate,
+            recent_balances,
+            notification_balance_history,
+            cycle_budget,
+            rpc_deadline,
+            balance_commitment,
+            finalized_recheck: cfg.finalized_recheck.unwrap_or(true)
+                && balance_commitment.commitment != CommitmentLevel::Finalized,
+            finalized_blockhash: cfg.finalized_blockhash.unwrap_or(false),
+            min_blockhash_validity,
+            max_send_attempts,
+            priority_fee,
+            repeated_failure_limit: cfg.repeated_failure_limit,
+            halt_on_repeated_failure: cfg.halt_on_repeated_failure.unwrap_or(false),
+            failure_streak: None,
+            halted: false,
+            max_transfers_per_hour: cfg.max_transfers_per_hour,
+            halt_on_excessive_transfers: cfg.halt_on_excessive_transfers.unwrap_or(false),
+            recent_transfers: VecDeque::new(),
+            transfer_rate_alerted: false,
+            dry_spell_alert_multiple: cfg.dry_spell_alert_multiple,
+            dry_spell_alerted: false,
+            in_flight: None,
+            quorum_rpcs,
+            expected_genesis_hash,
+            quorum_balance_reads,
+            unconfirmed_balance: None,
+            quorum_tolerance_lamports: cfg
+                .quorum_tolerance_lamports
+                .unwrap_or(DEFAULT_QUORUM_TOLERANCE_LAMPORTS),
+            min_uptime: Duration::from_secs(cfg.min_uptime_seconds.unwrap_or(0)),
+            pause_file: cfg.pause_file.as_ref().map(PathBuf::from),
+            failover,
+            failover_active: None,
+            pause_when_delinquent: cfg.pause_when_delinquent.unwrap_or(false),
+            allowed_weekdays,
+            alert_on_zero_balance: cfg.alert_on_zero_balance.unwrap_or(true),
+            last_nonzero_balance: None,
+            sweep_per_epoch,
+            sweep_epoch_slot_offset: cfg.sweep_epoch_slot_offset.unwrap_or(0),
+            sweep_delay: cfg
+                .sweep_delay_after_excess_seconds
+                .map(Duration::from_secs),
+            excess_since: None,
+            keepalive_interval: cfg.keepalive_memo_interval_seconds.map(Duration::from_secs),
+            history_file: cfg.history_file.as_ref().map(PathBuf::from),
+            dry_run: false,
+            dry_run_notify: false,
+            profile: false,
+            history_rotation: cfg.history_max_bytes.map(|max_bytes| history::Rotation {
+                max_bytes,
+                keep_files: cfg.history_keep_files.unwrap_or(DEFAULT_HISTORY_KEEP_FILES),
+            }),
+            audit_sign: cfg.audit_sign.unwrap_or(false),
+            state_path,
+            state,
+            notifier,
+            metrics,
+            started_at,
+            last_transfer_at: started_at,
+            fee_report_interval,
+            fee_report_max_fee_fraction,
+            last_fee_report: None,
+            unsigned_output: None,
+            observe_only,
+            balance_stream: None,
+        })
+    }
+
+    /// Refuse to start if the receiver is a vote account, unless `allow_vote_account_receiver`
+    /// is set, and log which kind of account each receiver is. A receiver that cannot be looked
+    /// up is only logged; the vote account check repeats before every transfer.
+    pub fn check_receiver(&self) -> Result<()> {
+        if self.observe_only {
+            return Ok(());
+        }
+        for (_, receiver) in self.amount_bands.iter().chain(&self.tiers) {
+            self.check_vote_account_receiver(receiver)?;
+        }
+        for (_, _, receiver) in &self.receiver_windows {
+            self.check_vote_account_receiver(receiver)?;
+        }
+        if let Some(fallback) = &self.fallback_receiver {
+            self.check_vote_account_receiver(fallback)?;
+        }
+        self.check_vote_account_receiver(&self.receiver)
+    }
+
+    fn check_vote_account_receiver(&self, receiver: &Pubkey) -> Result<()> {
+        let owner = self.account_owner(receiver);
+        if let Ok(owner) = &owner {
+            info!(
+                "Receiver account: receiver = {}, kind = {}",
+                redact::addr(receiver),
+                receiver_kind(receiver, *owner)
+            );
+        }
+        match owner.map(|owner| owner == Some(VOTE_PROGRAM_ID)) {
+            Ok(false) => Ok(()),
+            Ok(true) if self.allow_vote_account_receiver => {
+                warn!(
+                    "Receiver is a vote account; transferring anyway (allow_vote_account_receiver): receiver = {}",
+                    redact::addr(receiver)
+                );
+                Ok(())
+            }
+            Ok(true) => Err(anyhow!(
+                "receiver {} is a vote account (owned by the vote program), likely confused with the identity or treasury; refusing to start (set allow_vote_account_receiver to override)",
+                receiver
+            )),
+            Err(e) => {
+                warn!(
+                    "Failed to look up receiver account; skipping vote account check: {:#}",
+                    e
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Renew (or take over) the failover lock and report whether this instance may transfer.
+    /// Promotion and demotion are logged and alerted; without failover this is always true.
+    async fn refresh_failover(&mut self) -> bool {
+        let Some(lock) = &self.failover else {
+            return true;
+        };
+        let active = match lock.refresh() {
+            Ok(active) => active,
+            Err(e) => {
+                warn!(
+                    "Failed to refresh failover lock; acting as standby: {:#}",
+                    e
+                );
+                false
+            }
+        };
+        let holder = if active {
+            None
+        } else {
+            lock.holder().ok().flatten()
+        };
+        let previous = self.failover_active.replace(active);
+        if previous == Some(active) {
+            return active;
+        }
+        let holder = holder.as_deref().unwrap_or("nobody");
+        if active {
+            info!("Holding the failover lock; this instance transfers");
+        } else {
+            info!(
+                "Failover lock held elsewhere; standing by: holder = {}",
+                holder
+            );
+        }
+        // The role at startup is only logged; changes are alerted
+        if previous.is_some() {
+            let msg = if active {
+                "Promoted to active: the failover lock was stale or free and this instance took it over".to_string()
+            } else {
+                format!(
+                    "Demoted to standby: the failover lock is held by {}; this instance no longer transfers",
+                    holder
+                )
+            };
+            self.notifier.notify(notifier::Kind::Failover, &msg).await;
+        }
+        active
+    }
+
+    /// Request manual approval of a transfer of `amount` to `receiver` and wait for it. Once
+    /// approved, the finalized balance is re-read since it may have changed during the wait.
+    async fn await_approval(
+        &self,
+        cycle: &Cycle,
+        receiver: &Pubkey,
+        threshold: u64,
+        amount: u64,
+        sequence: u64,
+    ) -> bool {
+        let Some(approval) = &self.approval else {
+            return true;
+        };
+        // The amount is part of the id, so an approval never carries over to a different amount
+        let id = format!("sweep-{}-{}", sequence, amount);
+        info!(
+            "Transfer awaits manual approval: request = {}, amount_sol = {}, receiver = {}, timeout_s = {}",
+            id,
+            amount::sol(amount),
+            redact::addr(receiver),
+            approval.timeout.as_secs()
+        );
+        let msg = format!(
+            "Approval required: transfer of {} to {} (request {}). To approve, {} within {}s.",
+            amount::exact(amount),
+            receiver,
+            id,
+            approval.instructions(&id),
+            approval.timeout.as_secs()
+        );
+        self.notifier
+            .notify(notifier::Kind::ApprovalRequest, &msg)
+            .await;
+
+        // The wait ends with the cycle budget, whichever comes first
+        let decision = match cycle.remaining() {
+            Some(remaining) => timeout(remaining, approval.wait(&id))
+                .await
+                .unwrap_or(approval::Decision::TimedOut),
+            None => approval.wait(&id).await,
+        };
+        match decision {
+            approval::Decision::Approved => {
+                info!("Transfer approved: request = {}", id);
+                self.finalized_balance_covers(cycle, threshold, amount)
+            }
+            decision => {
+                warn!(
+                    "Transfer not approved; sweep cancelled until the next cycle: request = {}, decision = {:?}",
+                    id, decision
+                );
+                let msg = format!(
+                    "Transfer of {} to {} cancelled (request {}): {}",
+                    amount::exact(amount),
+                    receiver,
+                    id,
+                    match decision {
+                        approval::Decision::Rejected => "rejected",
+                        _ => "approval timed out",
+                    }
+                );
+                self.notifier
+                    .notify(notifier::Kind::ApprovalRequest, &msg)
+                    .await;
+                false
+            }
+        }
+    }
+
+    /// Transfer-time receiver validation: the allowlist, then (unless allowed) the vote
+    /// account check.
+    fn validate_receiver(&self, cycle: &Cycle, receiver: &Pubkey) -> ReceiverCheck {
+        if !self.receiver_allowlist.is_empty() && !self.receiver_allowlist.contains(receiver) {
+            return ReceiverCheck::Refused("is not in receiver_allowlist");
+        }
+        if self.allow_vote_account_receiver {
+            return ReceiverCheck::Valid;
+        }
+        match traced_rpc!(cycle, "getAccountInfo", self.is_vote_account(receiver)) {
+            Ok(false) => ReceiverCheck::Valid,
+            Ok(true) => ReceiverCheck::Refused(
+                "is a vote account (set allow_vote_account_receiver to override)",
+            ),
+            Err(e) => {
+                warn!(
+                    "Failed to look up receiver account; deferring transfer: receiver = {}, error = {:#}",
+                    redact::addr(receiver),
+                    e
+                );
+                ReceiverCheck::Unknown
+            }
+        }
+    }
+
+    /// Whether `account` exists and is owned by the vote program.
+    fn is_vote_account(&self, account: &Pubkey) -> Result<bool> {
+        Ok(self.account_owner(account)? == Some(VOTE_PROGRAM_ID))
+    }
+
+    /// Owner program of `account`; `None` if it does not exist (yet).
+    fn account_owner(&self, account: &Pubkey) -> Result<Option<Pubkey>> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(account, self.rpc.commitment())?
+            .value
+            .map(|a| a.owner))
+    }
+
+    /// The RPC endpoints of the daemon by config key: `rpc_provider` and, if set,
+    /// `confirm_rpc_provider`.
+    fn endpoints(&self) -> Vec<(&'static str, &RpcClient)> {
+        let mut endpoints = vec![("rpc_provider", &self.rpc)];
+        if let Some(confirm_rpc) = &self.confirm_rpc {
+            endpoints.push(("confirm_rpc_provider", confirm_rpc));
+        }
+        endpoints
+    }
+
+    /// Endpoint that signature statuses are polled from.
+    fn confirm_rpc(&self) -> &RpcClient {
+        self.confirm_rpc.as_ref().unwrap_or(&self.rpc)
+    }
+
+    /// Startup pre-flight: each RPC node must be reachable and healthy (`getHealth`), report its
+    /// version (`getVersion`, checked against the enabled features) and current slot
+    /// (`getSlot`). Fails with a diagnostic instead of letting the first poll discover it hours
+    /// later.
+    pub fn preflight(&self) -> Result<()> {
+        for (key, rpc) in self.endpoints() {
+            let diagnose = |method: &str, e: ClientError| {
+                anyhow!(
+                    "RPC pre-flight failed: {} on {}: {}; check that the node is reachable, the URL is right and the node is caught up",
+                    method,
+                    key,
+                    e
+                )
+            };
+            rpc.get_health().map_err(|e| diagnose("getHealth", e))?;
+            let version = rpc.get_version().map_err(|e| diagnose("getVersion", e))?;
+            let slot = rpc.get_slot().map_err(|e| diagnose("getSlot", e))?;
+            info!(
+                "RPC pre-flight passed: endpoint = {}, health = ok, solana_core = {}, feature_set = {:?}, slot = {}",
+                key, version.solana_core, version.feature_set, slot
+            );
+            // Prioritization fees are only sampled from rpc_provider
+            check_rpc_version(
+                &version.solana_core,
+                key == "rpc_provider" && self.priority_fee.is_some(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Refuse to start when `rpc_provider` (or `confirm_rpc_provider`) serves a different cluster
+    /// than `expected_cluster`, e.g. devnet keys pointed at a mainnet node.
+    pub fn check_cluster(&self) -> Result<()> {
+        let Some(expected) = self.expected_genesis_hash else {
+            return Ok(());
+        };
+        for (key, rpc) in self.endpoints() {
+            let actual = rpc
+                .get_genesis_hash()
+                .with_context(|| format!("fetching genesis hash from {}", key))?;
+            if actual != expected {
+                return Err(anyhow!(
+                    "{} serves cluster {} but expected_cluster is {}; refusing to start",
+                    key,
+                    cluster_name(&actual),
+                    cluster_name(&expected)
+                ));
+            }
+        }
+        info!(
+            "Cluster check passed: cluster = {}",
+            cluster_name(&expected)
+        );
+        Ok(())
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn set_dry_run_notify(&mut self, dry_run_notify: bool) {
+        self.dry_run_notify = dry_run_notify;
+    }
+
+    pub fn set_profile(&mut self, profile: bool) {
+        self.profile = profile;
+    }
+
+    /// Write the transfer a cycle decides on to `output` ("-" for stdout) unsigned, instead of
+    /// sending it (`--build-unsigned`).
+    pub fn set_build_unsigned(&mut self, output: String) {
+        self.unsigned_output = Some(output);
+    }
+
+    pub fn next_poll_interval(&self) -> Duration {
+        self.next_poll_interval
+    }
+
+    /// The account whose balance is watched: the sender (or `watch_pubkey`).
+    #[cfg(feature = "geyser")]
+    pub fn sender(&self) -> Pubkey {
+        self.sender
+    }
+
+    #[cfg(feature = "geyser")]
+    pub fn balance_commitment(&self) -> CommitmentLevel {
+        self.balance_commitment.commitment
+    }
+
+    /// Let a balance subscription (`geyser_endpoint`) wake the poll loop; it publishes on the
+    /// returned channel, `None` while it is disconnected.
+    #[cfg(feature = "geyser")]
+    pub fn subscribe_balances(&mut self) -> watch::Sender<Option<u64>> {
+        let (updates, receiver) = watch::channel(None);
+        self.balance_stream = Some(BalanceStream {
+            updates: receiver,
+            last: None,
+            trigger: None,
+            woken_at: None,
+        });
+        updates
+    }
+
+    /// One-line summary of how the daemon will behave in `mode` ("daemon", "once", ...), for the
+    /// startup log. The threshold is "static" unless `config_account` can override it or
+    /// monitored stake, obligations or the rent reserve adjust it at run time.
+    pub fn banner(&self, mode: &str) -> String {
+        let threshold_kind = if self.config_account.is_some() {
+            "config_account"
+        } else if !self.monitored_stake_accounts.is_empty()
+            || !self.obligations.is_empty()
+            || self.reserve_rent_exempt
+        {
+            "dynamic"
+        } else {
+            "static"
+        };
+        let mut receivers: Vec<Pubkey> = std::iter::once(self.receiver)
+            .chain(self.fallback_receiver)
+            .chain(self.amount_bands.iter().map(|(_, r)| *r))
+            .chain(self.tiers.iter().map(|(_, r)| *r))
+            .chain(self.receiver_windows.iter().map(|(_, _, r)| *r))
+            .collect();
+        receivers.sort();
+        receivers.dedup();
+        let channels = self.notifier.channels();
+        format!(
+            "Mode: {} | Threshold: {} SOL ({}) | Poll: every {} | Receivers: {} | Notifications: {} | Dry-run: {}",
+            mode,
+            amount::sol(self.threshold_lamports),
+            threshold_kind,
+            humantime::format_duration(self.poll_interval),
+            receivers.len(),
+            if channels.is_empty() {
+                "none".to_string()
+            } else {
+                channels.join(",")
+            },
+            match (self.dry_run, self.dry_run_notify) {
+                (false, _) => "no",
+                (true, false) => "yes",
+                (true, true) => "yes (notify)",
+            }
+        )
+    }
+
+    /// Poll forever, one cycle per poll interval. SIGUSR1 cuts the current sleep short for an
+    /// immediate cycle; the schedule then continues from that cycle.
+    pub async fn run(mut self) {
+        let mut manual_trigger = match signal(SignalKind::user_defined1()) {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                warn!(
+                    "Failed to install SIGUSR1 handler; manual checks are unavailable: {}",
+                    e
+                );
+                None
+            }
+        };
+        let mut pause = self.next_poll_interval;
+        loop {
+            // Sleep until next check. This is a simple approach. Replace with leader-slot-aware logic if desired.
+            tokio::select! {
+                _ = sleep(pause) => {}
+                Some(()) = async { manual_trigger.as_mut()?.recv().await } => {
+                    info!("manual check triggered via SIGUSR1");
+                }
+                Some(balance) = async { Some(self.balance_stream.as_mut()?.crossed().await) } => {
+                    info!(
+                        "Streamed balance crossed the trigger; checking now: balance_sol = {}",
+                        amount::sol(balance)
+                    );
+                }
+            }
+            let interval = self.next_poll_interval;
+            let started = Instant::now();
+            self.check_cycle().await;
+            pause = self.after_cycle(interval, started.elapsed());
+        }
+    }
+
+    /// One cycle of `--simulate-balances`: `elapsed` after the previous one, against a mock RPC
+    /// reporting `balance` as the sender balance.
+    pub async fn simulate_cycle(&mut self, balance: u64, elapsed: Duration) -> CycleOutcome {
+        // Move every recorded instant back rather than waiting
+        let back = |t: Instant| t.checked_sub(elapsed).unwrap_or(t);
+        self.started_at = back(self.started_at);
+        self.last_transfer_at = back(self.last_transfer_at);
+        if let Some((last, at)) = self.last_balance {
+            self.last_balance = Some((last, back(at)));
+        }
+        if let Some((config, at)) = self.on_chain_config {
+            self.on_chain_config = Some((config, back(at)));
+        }
+        self.excess_since = self.excess_since.map(back);
+
+        // Nothing leaves the process: every endpoint reads the scripted balance, and the shared
+        // failover lock is neither renewed nor taken over
+        let mock = || {
+            let mut mocks = HashMap::new();
+            mocks.insert(
+                RpcRequest::GetBalance,
+                json!(Response {
+                    context: RpcResponseContext {
+                        slot: 1,
+                        api_version: None,
+                    },
+                    value: balance,
+                }),
+            );
+            RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+        };
+        self.rpc = mock();
+        self.confirm_rpc = self.confirm_rpc.as_ref().map(|_| mock());
+        for (_, rpc) in &mut self.quorum_rpcs {
+            *rpc = mock();
+        }
+        self.failover = None;
+        self.check_cycle().await
+    }
+
+    /// Detect a cycle that took longer than the poll interval it was scheduled with, and return
+    /// how long to sleep before the next one.
+    fn after_cycle(&self, interval: Duration, elapsed: Duration) -> Duration {
+        if elapsed <= interval {
+            return self.next_poll_interval;
+        }
+        let overrun = elapsed - interval;
+        self.metrics.cycle_overrun_total.inc();
+        warn!(
+            "Poll cycle overran the poll interval; the schedule is drifting: cycle_s = {}, interval_s = {}, overrun_s = {}",
+            elapsed.as_secs(),
+            interval.as_secs(),
+            overrun.as_secs()
+        );
+        if self.catch_up_overruns {
+            self.next_poll_interval.saturating_sub(overrun)
+        } else {
+            self.next_poll_interval
+        }
+    }
+
+    /// Check the balance once and transfer the excess (or send a keep-alive memo) if due.
+    pub async fn check_cycle(&mut self) -> CycleOutcome {
+        let cycle = Cycle::new(info_span!(
+            "poll_cycle",
+            balance_lamports = field::Empty,
+            excess_lamports = field::Empty,
+            amount_lamports = field::Empty,
+            signature = field::Empty,
+        ))
+        .with_budget(self.cycle_budget);
+        self.rpc_deadline.set(cycle.deadline);
+        let outcome = self.run_cycle(&cycle).await;
+        self.rpc_deadline.set(None);
+        if self.profile {
+            self.report_profile(&cycle);
+        }
+        self.report_fees();
+        self.check_dry_spell().await;
+        outcome
+    }
+
+    /// Note a confirmed sweep for the dry spell check. Called once per sweep, however many
+    /// transfers (chunks, tiers) it took, so the average interval is between sweeps.
+    fn record_sweep_time(&mut self) {
+        self.state.push_transfer_time(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+        save_state(&self.state, self.state_path.as_deref());
+        self.dry_spell_alerted = false;
+    }
+
+    /// Alert (once per dry spell) when the last sweep is longer ago than
+    /// `dry_spell_alert_multiple` times the average interval between the recent sweeps: rewards
+    /// may have stopped or the threshold may be set too high.
+    async fn check_dry_spell(&mut self) {
+        let Some(multiple) = self.dry_spell_alert_multiple else {
+            return;
+        };
+        if self.dry_spell_alerted {
+            return;
+        }
+        let (Some(mean), Some(&last)) = (
+            self.state.mean_transfer_interval(),
+            self.state.transfer_times.last(),
+        ) else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let since = now.saturating_sub(last);
+        if since as f64 <= mean as f64 * multiple {
+            return;
+        }
+        self.dry_spell_alerted = true;
+        let (since, mean) = (Duration::from_secs(since), Duration::from_secs(mean));
+        warn!(
+            "No transfer for unusually long: since_last_transfer = {}, average_interval = {}, dry_spell_alert_multiple = {}",
+            humantime::format_duration(since),
+            humantime::format_duration(mean),
+            multiple
+        );
+        let msg = format!(
+            "No transfer from {} for {}, over {} times the average interval of {} between its recent transfers. Rewards may have stopped (check the node's health) or the threshold may be set too high.",
+            self.sender,
+            humantime::format_duration(since),
+            multiple,
+            humantime::format_duration(mean)
+        );
+        self.notifier.notify(notifier::Kind::DrySpell, &msg).await;
+    }
+
+    /// Log the fee projection from `history_file` with the break-even poll interval, after the
+    /// first cycle and then every `fee_report_interval_seconds`.
+    fn report_fees(&mut self) {
+        let (Some(interval), Some(path)) = (self.fee_report_interval, self.history_file.as_deref())
+        else {
+            return;
+        };
+        if self
+            .last_fee_report
+            .is_some_and(|last| last.elapsed() < interval)
+        {
+            return;
+        }
+        self.last_fee_report = Some(Instant::now());
+        let projection = match history::read_all(path) {
+            Ok(entries) => history::fee_projection(&entries, SystemTime::now()),
+            Err(e) => {
+                warn!("Failed to read history for the fee projection: {:#}", e);
+                return;
+            }
+        };
+        let Some(projection) = projection else {
+            info!(
+                "Fee projection: fewer than 2 transfers in the last 30 days of history; nothing to project yet"
+            );
+            return;
+        };
+        let month = history::FEE_PROJECTION_WINDOW.as_secs_f64();
+        // Upper bound: every poll finds excess at this build-up rate and sweeps it
+        let projected_fees = month / self.poll_interval.as_secs_f64().max(1.0)
+            * projection.average_fee_lamports as f64;
+        let break_even = (projection.swept_lamports_per_month > 0).then(|| {
+            Duration::from_secs(
+                (projection.average_fee_lamports as f64
+                    / self.fee_report_max_fee_fraction
+                    / projection.swept_lamports_per_month as f64
+                    * month) as u64,
+            )
+        });
+        info!(
+            "Fee projection: transfers_per_month = {:.1}, fees_per_month_sol = {}, excess_per_month_sol = {}, poll_interval = {}, projected_fees_per_month_sol = {}, break_even_poll_interval = {}",
+            projection.transfers_per_month,
+            amount::sol(projection.fee_lamports_per_month),
+            amount::sol(projection.swept_lamports_per_month),
+            humantime::format_duration(self.poll_interval),
+            amount::sol(projected_fees as u64),
+            break_even.map_or("unknown".to_string(), |d| humantime::format_duration(d)
+                .to_string())
+        );
+        if break_even.is_some_and(|break_even| self.poll_interval < break_even) {
+            info!(
+                "Polling more often than break_even_poll_interval sweeps amounts whose fee is above fee_report_max_fee_fraction = {} of them; consider a longer poll interval",
+                self.fee_report_max_fee_fraction
+            );
+        }
+    }
+
+    /// Log (and record as metrics) how long each RPC call of `cycle` and the whole cycle took.
+    fn report_profile(&self, cycle: &Cycle) {
+        let total = cycle.started.elapsed();
+        let phases = cycle.phases.lock().unwrap();
+        for (method, elapsed) in phases.iter() {
+            self.metrics
+                .rpc_phase_seconds
+                .with_label_values(&[method])
+                .observe(elapsed.as_secs_f64());
+        }
+        self.metrics.cycle_seconds.observe(total.as_secs_f64());
+        let rpc: Duration = phases.iter().map(|(_, elapsed)| *elapsed).sum();
+        info!(
+            "Cycle profile: total_ms = {}, rpc_ms = {}, phases = [{}]",
+            total.as_millis(),
+            rpc.as_millis(),
+            phases
+                .iter()
+                .map(|(method, elapsed)| format!("{} {}ms", method, elapsed.as_millis()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    async fn run_cycle(&mut self, cycle: &Cycle) -> CycleOutcome {
+        // Heartbeat first, so a failing balance read does not let the lock go stale
+        let active = self.refresh_failover().await;
+
+        // Get balance, unless the streamed update that woke this cycle already carries it
+        let streamed = self
+            .balance_stream
+            .as_mut()
+            .and_then(|stream| stream.woken_at.take());
+        let read = match streamed {
+            Some(balance) => Ok(balance),
+            None => traced_rpc!(
+                cycle,
+                "getBalance",
+                self.rpc
+                    .get_balance_with_commitment(&self.sender, self.balance_commitment)
+                    .map(|r| r.value)
+            ),
+        };
+        let balance = match read {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("Failed to get balance; will retry next loop: {}", e);
+                return CycleOutcome::Deferred;
+            }
+        };
+        cycle.span.record("balance_lamports", balance);
+        self.unconfirmed_balance = (self.quorum_balance_reads > 1).then_some(balance);
+        self.metrics.observe_balance(lamports_to_sol(balance));
+        info!(
+            "Balance check: lamports = {}, sol = {}",
+            balance,
+            amount::sol(balance)
+        );
+        self.check_zero_balance(balance).await;
+
+        // Hold back extra reserves while monitored stake is deactivating
+        let Some(stake_reserve) = self.stake_deactivation_reserve(cycle) else {
+            return CycleOutcome::Deferred;
+        };
+        // Never sweep the sender below rent exemption
+        let Some(rent_reserve) = self.rent_exempt_reserve(cycle) else {
+            return CycleOutcome::Deferred;
+        };
+        let reserve = stake_reserve + rent_reserve + self.obligation_reserve();
+        let (base_threshold, base_trigger, poll_interval) = self.current_settings(cycle);
+        let threshold = base_threshold + reserve;
+        let trigger = base_trigger + reserve;
+        if let Some(stream) = &mut self.balance_stream {
+            stream.trigger = Some(trigger);
+        }
+        self.plan_next_poll(balance, trigger, poll_interval);
+        self.metrics.observe_cycle(
+            lamports_to_sol(threshold),
+            lamports_to_sol(balance.saturating_sub(threshold)),
+        );
+
+        // Balance monitor: metrics only, never a transaction
+        if self.observe_only {
+            if balance > trigger {
+                info!(
+                    "Observe only; excess not transferred: excess_sol = {}",
+                    amount::sol(balance - threshold)
+                );
+                return CycleOutcome::Deferred;
+            }
+            return CycleOutcome::Idle;
+        }
+
+        // Startup grace period: observe only, never transfer
+        let uptime = self.started_at.elapsed();
+        if uptime < self.min_uptime {
+            info!(
+                "Startup grace period active; no transfer this cycle: remaining_s = {}",
+                (self.min_uptime - uptime).as_secs()
+            );
+            return CycleOutcome::Deferred;
+        }
+
+        // Maintenance mode: observe only while the sentinel file exists
+        if self.pause_file.as_ref().is_some_and(|path| path.exists()) {
+            info!("paused (sentinel present); no transfer this cycle");
+            return CycleOutcome::Deferred;
+        }
+
+        // Warm standby: observe only while another instance holds the failover lock
+        if !active {
+            info!("Standby (failover lock held elsewhere); no transfer this cycle");
+            return CycleOutcome::Deferred;
+        }
+
+        if balance > trigger {
+            let excess = balance - threshold;
+            // Sweep delay: only act on an excess that has persisted
+            if let Some(delay) = self.sweep_delay {
+                let since = *self.excess_since.get_or_insert_with(Instant::now);
+                if since.elapsed() < delay {
+                    info!(
+                        "Excess detected; holding for sweep delay: excess_sol = {}, remaining_s = {}",
+                        amount::sol(excess),
+                        (delay - since.elapsed()).as_secs()
+                    );
+                    return CycleOutcome::Deferred;
+                }
+            }
+            let outcome = if self.tiers.is_empty() {
+                let receiver = self.band_receiver(excess);
+                self.sweep_excess(cycle, threshold, excess, receiver).await
+            } else {
+                self.sweep_tiers(cycle, threshold, balance).await
+            };
+            if matches!(outcome, CycleOutcome::Transferred(_)) {
+                self.excess_since = None;
+                self.record_sweep_time();
+            }
+            return outcome;
+        }
+
+        if self.excess_since.take().is_some() {
+            info!("Excess disappeared during sweep delay; pending sweep cancelled");
+        }
+
+        if balance > threshold {
+            info!(
+                "Excess below relative trigger, no transfer: excess_sol = {}, trigger_sol = {}",
+                amount::sol(balance - threshold),
+                amount::sol(trigger)
+            );
+        } else if self
+            .near_threshold_margin_lamports
+            .is_some_and(|margin| threshold - balance <= margin)
+        {
+            let shortfall = threshold - balance;
+            info!(
+                "Balance near threshold, no transfer: shortfall_sol = {}, threshold_sol = {}",
+                amount::sol(shortfall),
+                amount::sol(threshold)
+            );
+        }
+
+        if self
+            .keepalive_interval
+            .is_some_and(|i| self.last_transfer_at.elapsed() >= i)
+        {
+            if self.dry_run {
+                info!("Dry run: keep-alive memo due but not sent");
+                if self.dry_run_notify {
+                    self.notifier
+                        .notify(
+                            notifier::Kind::KeepAlive,
+                            "[DRY RUN] Keep-alive memo due; nothing was sent",
+                        )
+                        .await;
+                }
+                return CycleOutcome::Idle;
+            }
+            if self.keypair.is_none() {
+                info!("Keep-alive memo due but the sender keypair is kept offline; not sent");
+                return CycleOutcome::Idle;
+            }
+            self.send_keepalive(cycle).await
+        } else {
+            CycleOutcome::Idle
+        }
+    }
+
+    /// Update the accrual rate estimate with this cycle's balance and, in adaptive mode, choose
+    /// the interval before the next poll.
+    fn plan_next_poll(&mut self, balance: u64, trigger: u64, poll_interval: Duration) {
+        let now = Instant::now();
+        if let Some((last, at)) = self.last_balance {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            // Decreases are transfers or spending, not accrual; keep the previous estimate
+            if balance > last && elapsed > 0.0 {
+                self.accrual_rate = Some((balance - last) as f64 / elapsed);
+            }
+        }
+        self.last_balance = Some((balance, now));
+        let sample = BalanceSample {
+            lamports: balance,
+            unix_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        self.recent_balances.push(sample);
+        let excess = self
+            .recent_balances
+            .len()
+            .saturating_sub(MAX_BALANCE_SAMPLES);
+        self.recent_balances.drain(..excess);
+
+        if let Some(bounds) = self.adaptive_polling {
+            // Persist the reading so a restarted daemon keeps its estimate; dry runs (and
+            // simulations, whose clock is not the wall clock) leave the state file alone
+            if !self.dry_run {
+                self.state.push_balance_sample(sample);
+                save_state(&self.state, self.state_path.as_deref());
+            }
+            self.next_poll_interval = bounds.next_interval(balance, trigger, self.accrual_rate);
+            info!(
+                "Next poll chosen: interval_s = {}, accrual_sol_per_hour = {}",
+                self.next_poll_interval.as_secs(),
+                amount::sol(self.accrual_rate.map_or(0, |r| (r * 3600.0) as u64))
+            );
+        } else {
+            self.next_poll_interval = poll_interval;
+        }
+    }
+
+    /// Sweep the `tiers` waterfall for `balance`, highest tier first. Stops at the first tier
+    /// that is not transferred; the rest is reconsidered next cycle from the new balance.
+    async fn sweep_tiers(&mut self, cycle: &Cycle, threshold: u64, balance: u64) -> CycleOutcome {
+        let transfers = tier_transfers(&self.tiers, threshold, balance);
+        for (floor, amount, receiver) in &transfers {
+            info!(
+                "Tier computed: floor_sol = {}, amount_sol = {}, receiver = {}",
+                amount::sol(*floor),
+                amount::sol(*amount),
+                redact::addr(receiver)
+            );
+        }
+        if transfers.is_empty() {
+            info!(
+                "Balance below the lowest tier; holding: balance_sol = {}, lowest_tier_sol = {}",
+                amount::sol(balance),
+                amount::sol(self.tiers[0].0)
+            );
+            return CycleOutcome::Idle;
+        }
+
+        let swept_epoch = self.state.last_swept_epoch;
+        let mut outcome = CycleOutcome::Idle;
+        let mut would_transfer = 0;
+        for (i, (floor, amount, receiver)) in transfers.into_iter().enumerate() {
+            if i > 0 {
+                // Per-epoch mode: the whole waterfall is one sweep
+                self.state.last_swept_epoch = swept_epoch;
+            }
+            // Each tier's balance is what remains once the higher tiers are swept
+            match self.sweep_excess(cycle, floor, amount, receiver).await {
+                CycleOutcome::Transferred(sig) => outcome = CycleOutcome::Transferred(sig),
+                CycleOutcome::WouldTransfer(lamports) => {
+                    would_transfer += lamports;
+                    outcome = CycleOutcome::WouldTransfer(would_transfer);
+                }
+                other => {
+                    if outcome == CycleOutcome::Idle {
+                        outcome = other;
+                    }
+                    break;
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Receiver of a transfer of `excess`: that of the highest amount band it reaches, or
+    /// `receiver_pubkey` without bands or below the lowest one.
+    fn band_receiver(&self, excess: u64) -> Pubkey {
+        if self.amount_bands.is_empty() {
+            return self.default_receiver(SystemTime::now());
+        }
+        match self
+            .amount_bands
+            .iter()
+            .rev()
+            .find(|(min, _)| excess >= *min)
+        {
+            Some((min, receiver)) => {
+                info!(
+                    "Amount band selected: band_min_sol = {}, receiver = {}",
+                    amount::sol(*min),
+                    redact::addr(receiver)
+                );
+                *receiver
+            }
+            None => {
+                info!("Excess below the lowest amount band; using the default receiver");
+                self.default_receiver(SystemTime::now())
+            }
+        }
+    }
+
+    /// Receiver of excess not routed by size at time `now`: that of the `receiver_windows`
+    /// entry covering it, else `receiver_pubkey`.
+    fn default_receiver(&self, now: SystemTime) -> Pubkey {
+        match self
+            .receiver_windows
+            .iter()
+            .find(|(from, to, _)| *from <= now && now < *to)
+        {
+            Some((from, to, receiver)) => {
+                info!(
+                    "Receiver window active: from = {}, to = {}, receiver = {}",
+                    humantime::format_rfc3339_seconds(*from),
+                    humantime::format_rfc3339_seconds(*to),
+                    redact::addr(receiver)
+                );
+                *receiver
+            }
+            None => {
+                if !self.receiver_windows.is_empty() {
+                    info!(
+                        "No receiver window active; using receiver_pubkey: receiver = {}",
+                        redact::addr(self.receiver)
+                    );
+                }
+                self.receiver
+            }
+        }
+    }
+
+    /// Whether the finalized sender balance still holds `amount` above `threshold`. Logs when it
+    /// disagrees with the cycle's lower-commitment balance read.
+    fn finalized_balance_covers(&self, cycle: &Cycle, threshold: u64, amount: u64) -> bool {
+        if cycle.out_of_budget("re-reading the finalized balance") {
+            return false;
+        }
+        let finalized = match traced_rpc!(
+            cycle,
+            "getBalance",
+            self.rpc
+                .get_balance_with_commitment(&self.sender, CommitmentConfig::finalized())
+        ) {
+            Ok(response) => response.value,
+            Err(e) => {
+                warn!("Failed to get finalized balance; deferring transfer: {}", e);
+                return false;
+            }
+        };
+        let excess = finalized.saturating_sub(threshold);
+        if excess < amount {
+            warn!(
+                "Finalized balance disagrees and no longer covers the transfer; deferring: commitment = {:?}, finalized_sol = {}, finalized_excess_sol = {}, amount_sol = {}",
+                self.balance_commitment.commitment,
+                amount::sol(finalized),
+                amount::sol(excess),
+                amount::sol(amount)
+            );
+            return false;
+        }
+        info!(
+            "Finalized balance covers the transfer: finalized_sol = {}",
+            amount::sol(finalized)
+        );
+        true
+    }
+
+    /// Re-read the sender balance from every quorum endpoint and check that at least
+    /// `quorum_balance_reads` reads, `balance` from `rpc_provider` included, agree with it.
+    fn balance_quorum_agrees(&self, cycle: &Cycle, balance: u64) -> bool {
+        let mut agreeing = 1;
+        for (url, rpc) in &self.quorum_rpcs {
+            if cycle.out_of_budget("quorum balance reads") {
+                return false;
+            }
+            match traced_rpc!(
+                cycle,
+                "getBalance",
+                rpc.get_balance_with_commitment(&self.sender, self.balance_commitment)
+            ) {
+                Ok(response)
+                    if response.value.abs_diff(balance) <= self.quorum_tolerance_lamports =>
+                {
+                    agreeing += 1
+                }
+                Ok(response) => warn!(
+                    "Quorum RPC disagrees on the sender balance: endpoint = {}, balance_sol = {}, rpc_provider_balance_sol = {}",
+                    url,
+                    amount::sol(response.value),
+                    amount::sol(balance)
+                ),
+                Err(e) => warn!(
+                    "Failed to get balance from quorum RPC: endpoint = {}, error = {}",
+                    url, e
+                ),
+            }
+        }
+        if agreeing < self.quorum_balance_reads {
+            warn!(
+                "Balance quorum not reached; deferring transfer: agreeing = {}, required = {}",
+                agreeing, self.quorum_balance_reads
+            );
+            return false;
+        }
+        info!(
+            "Balance quorum reached: agreeing = {}, required = {}",
+            agreeing, self.quorum_balance_reads
+        );
+        true
+    }
+
+    /// Threshold, trigger and poll interval (without stake reserves) for this cycle: those set
+    /// by `config_account`, falling back to the local config.
+    fn current_settings(&mut self, cycle: &Cycle) -> (u64, u64, Duration) {
+        let on_chain = self.read_config_account(cycle);
+        let (threshold, trigger) = match on_chain.and_then(|c| c.threshold_lamports) {
+            Some(threshold) => (
+                threshold,
+                threshold + (threshold as f64 * self.trigger_relative_percent / 100.0) as u64,
+            ),
+            None => (self.threshold_lamports, self.trigger_lamports),
+        };
+        let poll_interval = on_chain
+            .and_then(|c| c.poll_interval)
+            .unwrap_or(self.poll_interval);
+        (threshold, trigger, poll_interval)
+    }
+
+    /// Settings from `config_account`, read at most once per `CONFIG_ACCOUNT_CACHE_TTL`.
+    /// `None` without a config account or while it is unavailable or malformed.
+    fn read_config_account(&mut self, cycle: &Cycle) -> Option<OnChainConfig> {
+        let account = self.config_account?;
+        if let Some((cached, read_at)) = self.on_chain_config {
+            if read_at.elapsed() < CONFIG_ACCOUNT_CACHE_TTL {
+                return cached;
+            }
+        }
+        let read = traced_rpc!(cycle, "getAccountInfo", self.rpc.get_account_data(&account))
+            .map_err(anyhow::Error::from)
+            .and_then(|data| config_account::parse(&data));
+        let config = match read {
+            Ok(config) => {
+                info!(
+                    "Read config account: account = {}, threshold_sol = {:?}, poll_interval_s = {:?}",
+                    redact::addr(account),
+                    config.threshold_lamports.map(lamports_to_sol),
+                    config.poll_interval.map(|i| i.as_secs())
+                );
+                Some(config)
+            }
+            Err(e) => {
+                warn!(
+                    "Config account unavailable; using local config: account = {}, error = {:#}",
+                    redact::addr(account),
+                    e
+                );
+                None
+            }
+        };
+        self.on_chain_config = Some((config, Instant::now()));
+        config
+    }
+
+    /// A zero balance after a nonzero one usually means a wrong account or a drained identity,
+    /// not a quiet period: alert once per drop.
+    async fn check_zero_balance(&mut self, balance: u64) {
+        if balance != 0 {
+            self.last_nonzero_balance = Some(balance);
+            return;
+        }
+        let Some(previous) = self.last_nonzero_balance.take() else {
+            return;
+        };
+        if !self.alert_on_zero_balance {
+            return;
+        }
+        error!(
+            "Sender balance dropped to zero: previous_sol = {}",
+            amount::sol(previous)
+        );
+        let msg = format!(
+            "CRITICAL: sender balance dropped to zero: {} held {} SOL at the previous check. Check the sender account and rpc_provider.",
+            self.sender,
+            amount::sol(previous)
+        );
+        self.notifier
+            .notify(notifier::Kind::BalanceZero, &msg)
+            .await;
+    }
+
+    /// Sum (lamports) of the scheduled obligations due within the horizon, logged each cycle.
+    /// Obligations whose date has passed are dropped.
+    fn obligation_reserve(&mut self) -> u64 {
+        if self.obligations.is_empty() {
+            return 0;
+        }
+        let now = SystemTime::now();
+        self.obligations.retain(|(due, lamports)| {
+            let pending = *due > now;
+            if !pending {
+                info!(
+                    "Scheduled obligation date passed; no longer retained: date = {}, amount_sol = {}",
+                    humantime::format_rfc3339_seconds(*due),
+                    amount::sol(*lamports)
+                );
+            }
+            pending
+        });
+        let (count, total) = self
+            .obligations
+            .iter()
+            .filter(|(due, _)| {
+                due.duration_since(now).unwrap_or_default() <= self.obligation_horizon
+            })
+            .fold((0, 0), |(count, total), (_, lamports)| {
+                (count + 1, total + lamports)
+            });
+        info!(
+            "Scheduled obligations retained: count = {}, total_sol = {}",
+            count,
+            amount::sol(total)
+        );
+        total
+    }
+
+    /// Rent-exempt minimum (lamports) to keep on top of the threshold, or 0 with
+    /// `reserve_rent_exempt = false`. The sender holds no data (system transfers require that),
+    /// so this is the minimum for a zero-length account; it is read once and cached. `None`
+    /// (logged) if it could not be read; the cycle is then deferred.
+    fn rent_exempt_reserve(&mut self, cycle: &Cycle) -> Option<u64> {
+        if !self.reserve_rent_exempt {
+            return Some(0);
+        }
+        if let Some(minimum) = self.rent_exempt_minimum {
+            return Some(minimum);
+        }
+        match traced_rpc!(
+            cycle,
+            "getMinimumBalanceForRentExemption",
+            self.rpc.get_minimum_balance_for_rent_exemption(0)
+        ) {
+            Ok(minimum) => {
+                info!(
+                    "Keeping the sender rent exempt: rent_exempt_minimum_lamports = {}",
+                    minimum
+                );
+                self.rent_exempt_minimum = Some(minimum);
+                Some(minimum)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to get rent-exempt minimum; will retry next loop: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Extra reserve (lamports) to add to the threshold this cycle: `deactivation_reserve_sol`
+    /// while the monitored stake accounts' combined deactivating stake is significant, else 0.
+    /// `None` (logged) if activation could not be determined; the cycle is then deferred rather
+    /// than risk sweeping reserves that may be needed.
+    fn stake_deactivation_reserve(&self, cycle: &Cycle) -> Option<u64> {
+        if self.monitored_stake_accounts.is_empty() {
+            return Some(0);
+        }
+        if cycle.out_of_budget("reading stake activation") {
+            return None;
+        }
+        let deactivating = match self.deactivating_stake(cycle) {
+            Ok(lamports) => lamports,
+            Err(e) => {
+                warn!(
+                    "Failed to determine stake activation; deferring transfer: {:#}",
+                    e
+                );
+                return None;
+            }
+        };
+        if deactivating == 0 || deactivating < self.deactivating_stake_min_lamports {
+            return Some(0);
+        }
+        info!(
+            "Raising threshold while monitored stake deactivates: deactivating_sol = {}, reserve_sol = {}, threshold_sol = {}",
+            amount::sol(deactivating),
+            amount::sol(self.deactivation_reserve_lamports),
+            amount::sol(self.threshold_lamports + self.deactivation_reserve_lamports)
+        );
+        Some(self.deactivation_reserve_lamports)
+    }
+
+    /// Combined deactivating stake (lamports) of the monitored stake accounts this epoch.
+    fn deactivating_stake(&self, cycle: &Cycle) -> Result<u64> {
+        let epoch = traced_rpc!(cycle, "getEpochInfo", self.rpc.get_epoch_info())?.epoch;
+        let history = traced_rpc!(
+            cycle,
+            "getAccountInfo",
+            self.rpc
+                .get_account(&solana_stake_interface::stake_history::id())
+        )?;
+        let history = stake::parse_stake_history(&history.data)?;
+        let new_rate_activation_epoch = match traced_rpc!(
+            cycle,
+            "getAccountInfo",
+            self.rpc
+                .get_feature_activation_slot(&stake::REDUCE_STAKE_WARMUP_COOLDOWN)
+        )? {
+            Some(slot) => Some(
+                traced_rpc!(cycle, "getEpochSchedule", self.rpc.get_epoch_schedule())?
+                    .get_epoch(slot),
+            ),
+            None => None,
+        };
+        let accounts = traced_rpc!(
+            cycle,
+            "getMultipleAccounts",
+            self.rpc
+                .get_multiple_accounts(&self.monitored_stake_accounts)
+        )?;
+        let mut total = 0;
+        for (pubkey, account) in self.monitored_stake_accounts.iter().zip(accounts) {
+            let Some(account) = account else {
+                warn!(
+                    "Monitored stake account not found: account = {}",
+                    redact::addr(pubkey)
+                );
+                continue;
+            };
+            total += stake::deactivating_lamports(
+                &account.data,
+                epoch,
+                &history,
+                new_rate_activation_epoch,
+            )
+            .with_context(|| format!("stake account {}", pubkey))?;
+        }
+        Ok(total)
+    }
+
+    /// Run the pre-transfer checks for `excess` lamports, then transfer and record it.
+    async fn sweep_excess(
+        &mut self,
+        cycle: &Cycle,
+        threshold: u64,
+        excess: u64,
+        receiver: Pubkey,
+    ) -> CycleOutcome {
+        let excess_sol = amount::sol(excess);
+        cycle.span.record("excess_lamports", excess);
+        info!(
+            "Excess detected; preparing transfer: excess_lamports = {}, excess_sol = {}",
+            excess, excess_sol
+        );
+
+        if self.halted {
+            warn!(
+                "Transfers halted after repeated identical failures or too many transfers; fix the cause from the alert and restart the daemon"
+            );
+            return CycleOutcome::Deferred;
+        }
+
+        // Business-day mode: hold the excess on excluded days
+        if !self.allowed_weekdays.is_empty() {
+            let today = weekday(SystemTime::now());
+            if !self.allowed_weekdays.contains(&today) {
+                info!(
+                    "Transfers not allowed today; holding excess: weekday = {}, allowed_weekdays = [{}]",
+                    WEEKDAYS[today],
+                    self.allowed_weekdays
+                        .iter()
+                        .map(|d| WEEKDAYS[*d])
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                return CycleOutcome::Deferred;
+            }
+        }
+
+        // A previous transaction may still land; never send another alongside it
+        if !self.settle_in_flight(cycle).await {
+            return CycleOutcome::Deferred;
+        }
+
+        // Per-epoch mode: at most one sweep per epoch, once far enough into it
+        let mut sweep_epoch = None;
+        if self.sweep_per_epoch {
+            let epoch_info = match traced_rpc!(cycle, "getEpochInfo", self.rpc.get_epoch_info()) {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!("Failed to get epoch info; will retry next loop: {}", e);
+                    return CycleOutcome::Deferred;
+                }
+            };
+            if self.state.last_swept_epoch == Some(epoch_info.epoch) {
+                info!(
+                    "Already swept this epoch; waiting for the next: epoch = {}",
+                    epoch_info.epoch
+                );
+                return CycleOutcome::Deferred;
+            }
+            if epoch_info.slot_index < self.sweep_epoch_slot_offset {
+                info!(
+                    "Too early in epoch to sweep: epoch = {}, slot_index = {}, sweep_epoch_slot_offset = {}",
+                    epoch_info.epoch, epoch_info.slot_index, self.sweep_epoch_slot_offset
+                );
+                return CycleOutcome::Deferred;
+            }
+            sweep_epoch = Some(epoch_info.epoch);
+        }
+
+        // Hold reserves while our validator is delinquent; it needs them to catch up on voting
+        if self.pause_when_delinquent {
+            match traced_rpc!(cycle, "getVoteAccounts", self.rpc.get_vote_accounts()) {
+                Ok(status) => {
+                    let identity = self.sender.to_string();
+                    if status.delinquent.iter().any(|v| v.node_pubkey == identity) {
+                        warn!(
+                            "Validator is delinquent; deferring transfer: identity = {}",
+                            redact::addr(&identity)
+                        );
+                        let msg = format!(
+                            "Validator {} is delinquent; holding {} SOL excess until it recovers",
+                            identity, excess_sol
+                        );
+                        self.notifier.notify(notifier::Kind::Delinquent, &msg).await;
+                        return CycleOutcome::Deferred;
+                    }
+                    if !status.current.iter().any(|v| v.node_pubkey == identity) {
+                        warn!(
+                            "Sender identity not found among vote accounts; delinquency check skipped: identity = {}",
+                            redact::addr(&identity)
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to get vote accounts; deferring transfer: {}", e);
+                    return CycleOutcome::Deferred;
+                }
+            }
+        }
+
+        // The receiver balance is only needed by the fill model and the sanity ceiling
+        let receiver_balance = if self.receiver_target_lamports.is_some()
+            || self.receiver_max_expected_lamports.is_some()
+        {
+            match traced_rpc!(cycle, "getBalance", self.rpc.get_balance(&receiver)) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    warn!(
+                        "Failed to get receiver balance; will retry next loop: {}",
+                        e
+                    );
+                    return CycleOutcome::Deferred;
+                }
+            }
+        } else {
+            None
+        };
+
+        // A receiver already holding more than expected is likely the wrong account
+        if let (Some(max), Some(receiver_balance)) =
+            (self.receiver_max_expected_lamports, receiver_balance)
+        {
+            if receiver_balance > max {
+                error!(
+                    "Receiver balance unexpectedly high, possible misconfig; skipping transfer: receiver = {}, receiver_sol = {}, max_expected_sol = {}",
+                    redact::addr(receiver),
+                    amount::sol(receiver_balance),
+                    amount::sol(max)
+                );
+                let msg = format!(
+                    "Receiver {} balance unexpectedly high ({} SOL > {} SOL expected), possible misconfig; transfer of {} SOL skipped",
+                    receiver,
+                    amount::sol(receiver_balance),
+                    amount::sol(max),
+                    excess_sol
+                );
+                self.notifier
+                    .notify(notifier::Kind::ReceiverBalanceHigh, &msg)
+                    .await;
+                return CycleOutcome::Deferred;
+            }
+        }
+
+        // Fill model: only send what the receiver is short of its target
+        let amount = match (self.receiver_target_lamports, receiver_balance) {
+            (Some(target), Some(receiver_balance)) => {
+                if receiver_balance >= target {
+                    info!(
+                        "Receiver at or above target; no transfer: receiver_sol = {}, target_sol = {}",
+                        amount::sol(receiver_balance),
+                        amount::sol(target)
+                    );
+                    return CycleOutcome::Deferred;
+                }
+                let shortfall = target - receiver_balance;
+                info!(
+                    "Receiver below target: receiver_sol = {}, shortfall_sol = {}",
+                    amount::sol(receiver_balance),
+                    amount::sol(shortfall)
+                );
+                shortfall.min(excess)
+            }
+            _ => excess,
+        };
+        let amount = match self.first_transfer_max_lamports {
+            Some(cap) => {
+                info!(
+                    "First-transfer cap in effect: amount_sol = {}, cap_sol = {}",
+                    amount::sol(amount),
+                    amount::sol(cap)
+                );
+                amount.min(cap)
+            }
+            None => amount,
+        };
+        let amount_sol = amount::sol(amount);
+        cycle.span.record("amount_lamports", amount);
+
+        if cycle.out_of_budget("fetching blockhash") {
+            return CycleOutcome::Deferred;
+        }
+
+        let receiver = match self.validate_receiver(cycle, &receiver) {
+            ReceiverCheck::Valid => receiver,
+            ReceiverCheck::Unknown => return CycleOutcome::Deferred,
+            ReceiverCheck::Refused(reason) => {
+                error!(
+                    "Receiver refused; aborting transfer to it: receiver = {}, reason = {}",
+                    redact::addr(receiver),
+                    reason
+                );
+                let Some(fallback) = self.fallback_receiver.filter(|f| *f != receiver) else {
+                    let msg = format!(
+                        "CRITICAL: transfer of {} SOL aborted, receiver {} {}",
+                        amount_sol, receiver, reason
+                    );
+                    self.notifier
+                        .notify(notifier::Kind::ReceiverRefused, &msg)
+                        .await;
+                    return CycleOutcome::Deferred;
+                };
+                match self.validate_receiver(cycle, &fallback) {
+                    ReceiverCheck::Valid => {
+                        warn!(
+                            "Routing transfer to fallback receiver: fallback_receiver = {}",
+                            redact::addr(fallback)
+                        );
+                        let msg = format!(
+                            "CRITICAL: receiver {} {}; transfer of {} SOL routed to fallback_receiver_pubkey {} instead",
+                            receiver, reason, amount_sol, fallback
+                        );
+                        self.notifier
+                            .notify(notifier::Kind::ReceiverRefused, &msg)
+                            .await;
+                        fallback
+                    }
+                    ReceiverCheck::Unknown => return CycleOutcome::Deferred,
+                    ReceiverCheck::Refused(fallback_reason) => {
+                        error!(
+                            "Fallback receiver refused too; aborting transfer: fallback_receiver = {}, reason = {}",
+                            redact::addr(fallback),
+                            fallback_reason
+                        );
+                        let msg = format!(
+                            "CRITICAL: transfer of {} SOL aborted, receiver {} {} and fallback_receiver_pubkey {} {}",
+                            amount_sol, receiver, reason, fallback, fallback_reason
+                        );
+                        self.notifier
+                            .notify(notifier::Kind::ReceiverRefused, &msg)
+                            .await;
+                        return CycleOutcome::Deferred;
+                    }
+                }
+            }
+        };
+
+        // The balance was read below finalized commitment and may still be rolled back
+        if self.finalized_recheck && !self.finalized_balance_covers(cycle, threshold, amount) {
+            return CycleOutcome::Deferred;
+        }
+
+        let sequence = self.state.transfer_sequence + 1;
+        let needs_approval = self
+            .approval
+            .as_ref()
+            .is_some_and(|a| amount > a.threshold_lamports);
+
+        if self.dry_run {
+            info!(
+                "Dry run: would transfer: amount_sol = {}, amount_lamports = {}, receiver = {}, needs_approval = {}",
+                amount_sol,
+                amount,
+                redact::addr(receiver),
+                needs_approval
+            );
+            if self.dry_run_notify {
+                let msg = format!(
+                    "[DRY RUN] Would transfer {} from {} to {} (sweep #{}{}); nothing was sent",
+                    amount::exact(amount),
+                    self.sender,
+                    receiver,
+                    sequence,
+                    if needs_approval {
+                        ", after manual approval"
+                    } else {
+                        ""
+                    }
+                );
+                self.notifier.notify(notifier::Kind::Transfer, &msg).await;
+            }
+            return CycleOutcome::WouldTransfer(amount);
+        }
+
+        // Against the balance the cycle read; later tiers and chunks see it already lowered
+        if let Some(balance) = self.unconfirmed_balance.take() {
+            if !self.balance_quorum_agrees(cycle, balance) {
+                return CycleOutcome::Deferred;
+            }
+        }
+
+        if let Some(output) = self.unsigned_output.clone() {
+            // One transaction per signing round; later chunks follow in later rounds
+            let part = self.chunk_lamports.unwrap_or(amount).min(amount);
+            return match self.write_unsigned(cycle, &receiver, part, sequence, &output) {
+                Ok(()) => CycleOutcome::WouldTransfer(part),
+                Err(e) => {
+                    error!("Failed to build unsigned transfer: {:#}", e);
+                    CycleOutcome::Deferred
+                }
+            };
+        }
+
+        if needs_approval
+            && !self
+                .await_approval(cycle, &receiver, threshold, amount, sequence)
+                .await
+        {
+            return CycleOutcome::Deferred;
+        }
+
+        // The lock may have changed hands during the cycle (or a long approval wait)
+        if !self.refresh_failover().await {
+            warn!(
+                "Failover lock lost before sending; transfer cancelled: amount_sol = {}",
+                amount_sol
+            );
+            return CycleOutcome::Deferred;
+        }
+
+        // Chunked transfers: each chunk is its own sweep, confirmed before the next is sent
+        let chunk = self.chunk_lamports.unwrap_or(amount).min(amount);
+        if chunk < amount {
+            info!(
+                "Sending transfer in chunks: amount_sol = {}, chunk_sol = {}, chunks = {}",
+                amount_sol,
+                amount::sol(chunk),
+                amount.div_ceil(chunk)
+            );
+        }
+        let mut sent = Vec::new();
+        let mut remaining = amount;
+        while remaining > 0 && !self.halted {
+            let part = remaining.min(chunk);
+            let sequence = self.state.transfer_sequence + 1;
+            let memo = sweep_memo(sequence);
+            let (sig, fee_lamports, sent_slot) = match self.execute_transfer(
+                cycle,
+                &receiver,
+                part,
+                Some(&memo),
+            ) {
+                Ok(sent) => sent,
+                Err(TransferFailure::BudgetExhausted) => break,
+                Err(TransferFailure::Error(signature)) => {
+                    self.record_transfer_failure(signature).await;
+                    break;
+                }
+                Err(TransferFailure::Expired {
+                    error,
+                    attempts,
+                    signature,
+                }) => {
+                    let msg = format!(
+                        "CRITICAL: transfer of {} to {} (sweep #{}) failed to land after {} attempts: {}. It is retried next cycle",
+                        amount::exact(part),
+                        receiver,
+                        sequence,
+                        attempts,
+                        error
+                    );
+                    self.notifier
+                        .notify(notifier::Kind::LandingFailed, &msg)
+                        .await;
+                    self.record_failed_transfer(
+                        &signature,
+                        &receiver,
+                        part,
+                        &format!("failed to land after {} attempts: {}", attempts, error),
+                    );
+                    self.record_transfer_failure(error).await;
+                    break;
+                }
+                Err(TransferFailure::Preflight(signature)) => {
+                    let msg = format!(
+                        "Transfer of {} to {} (sweep #{}) was rejected by preflight simulation; nothing was sent: {}. It is retried next cycle with a fresh balance",
+                        amount::exact(part),
+                        receiver,
+                        sequence,
+                        signature
+                    );
+                    self.notifier
+                        .notify(notifier::Kind::PreflightFailed, &msg)
+                        .await;
+                    self.record_transfer_failure(signature).await;
+                    break;
+                }
+                Err(TransferFailure::Submitted {
+                    error,
+                    signature,
+                    blockhash,
+                }) => {
+                    let msg = format!(
+                        "CRITICAL: transfer of {} to {} (sweep #{}) failed after submission and may still land: {}. Signature: {}. No other transfer is sent until it lands or expires",
+                        amount::exact(part),
+                        receiver,
+                        sequence,
+                        error,
+                        signature
+                    );
+                    self.notifier
+                        .notify(notifier::Kind::SubmissionFailed, &msg)
+                        .await;
+                    self.in_flight = Some(InFlight {
+                        signature,
+                        blockhash,
+                        receiver,
+                        amount: part,
+                        sequence,
+                    });
+                    self.record_transfer_failure(error).await;
+                    break;
+                }
+            };
+            self.failure_streak = None;
+            let landed_slot = self
+                .record_transfer(cycle, &sig, &receiver, part, fee_lamports, sequence)
+                .await;
+            if let (Some(priority_fee), Some(sent_slot), Some(landed_slot)) =
+                (self.priority_fee.as_mut(), sent_slot, landed_slot)
+            {
+                let slots = landed_slot.saturating_sub(sent_slot);
+                priority_fee.observe(slots);
+                info!(
+                    "Priority fee outcome: confirmation_slots = {}, target_confirmation_slots = {}, adjustment = {:.2}",
+                    slots, priority_fee.target_slots, priority_fee.adjustment
+                );
+            }
+            sent.push((sig, sequence));
+            remaining -= part;
+        }
+        let Some(&(last_sig, last_sequence)) = sent.last() else {
+            return CycleOutcome::Deferred;
+        };
+        if remaining == 0 {
+            if let Some(epoch) = sweep_epoch {
+                self.state.last_swept_epoch = Some(epoch);
+                save_state(&self.state, self.state_path.as_deref());
+            }
+        } else {
+            warn!(
+                "Chunked transfer stopped early; the rest is retried next cycle: sent_sol = {}, remaining_sol = {}",
+                amount::sol(amount - remaining),
+                amount::sol(remaining)
+            );
+        }
+
+        // Slack notification (best-effort), one for all chunks
+        if !self.notifier.enabled() {
+            info!("Notifications disabled; skipping transfer notification");
+        } else {
+            let msg = if let [(sig, sequence)] = sent.as_slice() {
+                format!(
+                    "Transferred {amount} from {sender} to {receiver} (sweep #{sequence}). Signature: {sig}",
+                    amount = amount::exact(amount - remaining),
+                    sender = self.sender,
+                    receiver = receiver,
+                    sequence = sequence,
+                    sig = sig
+                )
+            } else {
+                format!(
+                    "Transferred {amount} from {sender} to {receiver} in {count} chunks (sweeps #{first}-#{last}). Signatures: {sigs}",
+                    amount = amount::exact(amount - remaining),
+                    sender = self.sender,
+                    receiver = receiver,
+                    count = sent.len(),
+                    first = sent[0].1,
+                    last = last_sequence,
+                    sigs = sent
+                        .iter()
+                        .map(|(sig, _)| sig.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+            let mut msg = if remaining > 0 {
+                format!(
+                    "{}. A chunk failed; {} SOL was not sent and is retried next cycle",
+                    msg,
+                    amount::sol(remaining)
+                )
+            } else {
+                msg
+            };
+            if self.notification_balance_history > 0 {
+                let skip = self
+                    .recent_balances
+                    .len()
+                    .saturating_sub(self.notification_balance_history);
+                msg.push('\n');
+                msg.push_str(&balance_history(&self.recent_balances[skip..]));
+            }
+            self.notifier.notify(notifier::Kind::Transfer, &msg).await;
+        }
+        self.last_transfer_at = Instant::now();
+        CycleOutcome::Transferred(last_sig)
+    }
+
+    /// Bookkeeping of one confirmed transfer: logs, receipt, metrics, state and history.
+    async fn record_transfer(
+        &mut self,
+        cycle: &Cycle,
+        sig: &Signature,
+        receiver: &Pubkey,
+        amount: u64,
+        fee_lamports: Option<u64>,
+        sequence: u64,
+    ) -> Option<u64> {
+        let sig_str = sig.to_string();
+        cycle
+            .span
+            .record("signature", redact::addr(&sig_str).to_string().as_str());
+        info!(
+            "Transfer confirmed: signature = {}, amount_sol = {}, amount_lamports = {}, sequence = {}",
+            redact::addr(&sig_str),
+            amount::sol(amount),
+            amount,
+            sequence
+        );
+        if self.first_transfer_max_lamports.take().is_some() {
+            info!("First transfer after startup confirmed; first-transfer cap lifted");
+        }
+        let receipt = self.fetch_receipt(cycle, sig, receiver).await;
+        if let Some(receipt) = &receipt {
+            let balances = |b: Option<(u64, u64)>| match b {
+                Some((pre, post)) => format!("{} -> {}", pre, post),
+                None => "unknown".to_string(),
+            };
+            info!(
+                "Transfer receipt: signature = {}, slot = {}, fee_lamports = {}, sender_lamports = {}, receiver_lamports = {}",
+                redact::addr(&sig_str),
+                receipt.slot,
+                receipt.fee_lamports,
+                balances(receipt.sender_balances),
+                balances(receipt.receiver_balances)
+            );
+        }
+        let fee_lamports = receipt.as_ref().map(|r| r.fee_lamports).or(fee_lamports);
+        let sender_fee_lamports = match self.fee_payer {
+            Some(_) => 0,
+            None => fee_lamports.unwrap_or(0),
+        };
+        self.metrics.observe_transfer(
+            lamports_to_sol(amount),
+            lamports_to_sol(sender_fee_lamports),
+        );
+        self.state.transfer_sequence = sequence;
+        save_state(&self.state, self.state_path.as_deref());
+        if let Some(path) = self.history_file.as_deref() {
+            let mut entry = history::HistoryEntry::new(
+                sig_str.clone(),
+                self.sender.to_string(),
+                receiver.to_string(),
+                amount,
+                fee_lamports,
+                receipt.as_ref().map(|r| r.slot),
+            );
+            entry.sequence = Some(sequence);
+            if self.audit_sign {
+                entry.sign(self.keypair.as_ref());
+            }
+            if let Err(e) = history::append(path, &entry, self.history_rotation) {
+                error!("Failed to record transfer in history: {:#}", e);
+            }
+        }
+        self.check_transfer_rate().await;
+        receipt.map(|r| r.slot)
+    }
+
+    /// Count the transfer just confirmed against `max_transfers_per_hour`; once exceeded, send a
+    /// critical alert (again only after the rate dropped back) and, with
+    /// `halt_on_excessive_transfers`, suspend transfers.
+    async fn check_transfer_rate(&mut self) {
+        let Some(limit) = self.max_transfers_per_hour else {
+            return;
+        };
+        let now = Instant::now();
+        self.recent_transfers.push_back(now);
+        while self
+            .recent_transfers
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= TRANSFER_RATE_WINDOW)
+        {
+            self.recent_transfers.pop_front();
+        }
+        let count = self.recent_transfers.len();
+        if count <= limit {
+            self.transfer_rate_alerted = false;
+            return;
+        }
+        if self.transfer_rate_alerted {
+            return;
+        }
+        self.transfer_rate_alerted = true;
+        self.halted |= self.halt_on_excessive_transfers;
+        error!(
+            "Transfers more frequent than expected: transfers_last_hour = {}, max_transfers_per_hour = {}, halted = {}",
+            count, limit, self.halted
+        );
+        let msg = format!(
+            "CRITICAL: {} transfers from {} in the last hour, above max_transfers_per_hour = {}. Check poll_interval and the threshold for a misconfiguration; each transfer pays a fee. {}",
+            count,
+            self.sender,
+            limit,
+            if self.halted {
+                "Transfers are halted until the daemon is restarted."
+            } else {
+                "Transfers continue."
+            }
+        );
+        self.notifier
+            .notify(notifier::Kind::ExcessiveTransfers, &msg)
+            .await;
+    }
+
+    /// Append a failed entry for a transfer of `amount` to `receiver` that could not land to the
+    /// history file. It carries no sweep sequence: the number is reused by the retry.
+    fn record_failed_transfer(
+        &self,
+        signature: &Signature,
+        receiver: &Pubkey,
+        amount: u64,
+        failure: &str,
+    ) {
+        let Some(path) = self.history_file.as_deref() else {
+            return;
+        };
+        let mut entry = history::HistoryEntry::new(
+            signature.to_string(),
+            self.sender.to_string(),
+            receiver.to_string(),
+            amount,
+            None,
+            None,
+        );
+        entry.failure = Some(failure.to_string());
+        if self.audit_sign {
+            entry.sign(self.keypair.as_ref());
+        }
+        if let Err(e) = history::append(path, &entry, self.history_rotation) {
+            error!("Failed to record failed transfer in history: {:#}", e);
+        }
+    }
+
+    /// Sign, send and confirm a transfer of `amount` lamports to `to`, with an optional memo. An
+    /// expired blockhash is refetched and the transfer re-signed and resent, up to
+    /// `max_send_attempts` sends; any other failure abandons the cycle. With
+    /// `target_confirmation_slots` the transfer carries a priority fee. Returns the signature,
+    /// the fee paid and, with a priority fee, the slot it was last sent at, where known.
+    fn execute_transfer(
+        &self,
+        cycle: &Cycle,
+        to: &Pubkey,
+        amount: u64,
+        memo: Option<&str>,
+    ) -> Result<(Signature, Option<u64>, Option<u64>), TransferFailure> {
+        let mut instructions = vec![system_instruction::transfer(&self.sender, to, amount)];
+        if let Some(memo) = memo {
+            instructions.push(memo_instruction(&self.sender, memo));
+        }
+        if let Some(priority_fee) = &self.priority_fee {
+            let recent_fees = match traced_rpc!(
+                cycle,
+                "getRecentPrioritizationFees",
+                self.rpc.get_recent_prioritization_fees(&[self.sender, *to])
+            ) {
+                Ok(fees) => fees.iter().map(|f| f.prioritization_fee).collect(),
+                Err(e) => {
+                    warn!(
+                        "Failed to sample recent prioritization fees; pricing from past confirmations only: {}",
+                        e
+                    );
+                    Vec::new()
+                }
+            };
+            let price = priority_fee.price(&recent_fees);
+            info!(
+                "Priority fee chosen: compute_unit_price_micro_lamports = {}, compute_unit_limit = {}, target_confirmation_slots = {}, sampled_slots = {}, adjustment = {:.2}",
+                price,
+                priority_fee::COMPUTE_UNIT_LIMIT,
+                priority_fee.target_slots,
+                recent_fees.len(),
+                priority_fee.adjustment
+            );
+            instructions.splice(0..0, priority_fee::instructions(price));
+        }
+        let mut attempt = 1;
+        loop {
+            let (recent_blockhash, last_valid_block_height) = match self.latest_blockhash(cycle) {
+                Ok(h) => h,
+                Err(e) => {
+                    error!("Failed to get recent blockhash: {}", e);
+                    return Err(TransferFailure::Error(format!("getLatestBlockhash: {}", e)));
+                }
+            };
+
+            if cycle.out_of_budget("sending transaction") {
+                return Err(TransferFailure::BudgetExhausted);
+            }
+
+            let tx = match self.signed_transaction(&instructions, recent_blockhash) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    error!("Failed to sign transaction: {:#}", e);
+                    return Err(TransferFailure::Error(e.to_string()));
+                }
+            };
+            let fee_lamports = match traced_rpc!(
+                cycle,
+                "getFeeForMessage",
+                self.rpc.get_fee_for_message(&tx.message)
+            ) {
+                Ok(fee) => Some(fee),
+                Err(e) => {
+                    warn!("Failed to get transaction fee: {}", e);
+                    None
+                }
+            };
+
+            // Confirmation time of a priority-fee transfer counts from the latest processed slot
+            let sent_slot = self.priority_fee.as_ref().and_then(|_| {
+                traced_rpc!(
+                    cycle,
+                    "getSlot",
+                    self.rpc
+                        .get_slot_with_commitment(CommitmentConfig::processed())
+                )
+                .ok()
+            });
+
+            // Send and confirm transaction
+            match self.send_and_confirm(cycle, &tx, last_valid_block_height) {
+                Ok(sig) => return Ok((sig, fee_lamports, sent_slot)),
+                Err(e) => match classify_send_error(&e) {
+                    SendFailure::BlockhashExpired if attempt < self.max_send_attempts => {
+                        warn!(
+                            "Transaction blockhash expired; refetching and resending: attempt = {}, error = {}",
+                            attempt, e
+                        );
+                        attempt += 1;
+                    }
+                    SendFailure::BlockhashExpired => {
+                        error!(
+                            "Transaction blockhash kept expiring; giving up: attempt = {}, error = {}",
+                            attempt, e
+                        );
+                        return Err(TransferFailure::Expired {
+                            error: failure_signature(&e),
+                            attempts: attempt,
+                            signature: tx.signatures[0],
+                        });
+                    }
+                    SendFailure::Preflight => {
+                        error!(
+                            "Transaction rejected by preflight simulation; nothing was sent: attempt = {}, error = {}",
+                            attempt, e
+                        );
+                        return Err(TransferFailure::Preflight(failure_signature(&e)));
+                    }
+                    SendFailure::Submission => {
+                        error!(
+                            "Transaction failed after submission; it may still land: signature = {}, attempt = {}, error = {}",
+                            redact::addr(tx.signatures[0]),
+                            attempt,
+                            e
+                        );
+                        return Err(TransferFailure::Submitted {
+                            error: failure_signature(&e),
+                            signature: tx.signatures[0],
+                            blockhash: recent_blockhash,
+                        });
+                    }
+                },
+            }
+        }
+    }
+
+    /// Write the transfer of `amount` to `receiver` (sweep `sequence`) to `output` as an unsigned
+    /// transaction for offline signing: base64 of the bincode-serialized transaction with blank
+    /// signatures, after `#` comment lines naming the transfer and its blockhash.
+    fn write_unsigned(
+        &self,
+        cycle: &Cycle,
+        receiver: &Pubkey,
+        amount: u64,
+        sequence: u64,
+        output: &str,
+    ) -> Result<()> {
+        let (blockhash, last_valid) = self
+            .latest_blockhash(cycle)
+            .context("getting recent blockhash")?;
+        let instructions = [
+            system_instruction::transfer(&self.sender, receiver, amount),
+            memo_instruction(&self.sender, &sweep_memo(sequence)),
+        ];
+        let mut tx = Transaction::new_with_payer(&instructions, Some(&self.sender));
+        tx.message.recent_blockhash = blockhash;
+        let encoded = BASE64_STANDARD.encode(bincode::serialize(&tx)?);
+        let text = format!(
+            "# automated-fund-transfer unsigned transfer: sweep #{}, {} from {} to {}\n# blockhash {}{}\n# Sign it offline with the sender keypair before the blockhash expires, then submit it with --broadcast\n{}\n",
+            sequence,
+            amount::exact(amount),
+            self.sender,
+            receiver,
+            blockhash,
+            last_valid.map_or(String::new(), |h| format!(
+                ", valid until block height {}",
+                h
+            )),
+            encoded
+        );
+        if output == "-" {
+            print!("{}", text);
+        } else {
+            fs::write(output, text).with_context(|| format!("writing {}", output))?;
+        }
+        info!(
+            "Unsigned transfer built for offline signing: amount_sol = {}, receiver = {}, sequence = {}, blockhash = {}, output = {}",
+            amount::sol(amount),
+            redact::addr(receiver),
+            sequence,
+            blockhash,
+            output
+        );
+        Ok(())
+    }
+
+    /// Submit and confirm a transfer signed offline from `--build-unsigned` output
+    /// (`--broadcast`), then record and notify it like any sweep.
+    pub async fn broadcast(&mut self, path: &Path) -> Result<()> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let tx = decode_transaction(&text).context("decoding signed transaction")?;
+        let (receiver, amount, sequence) = sweep_transfer(&tx, &self.sender).ok_or_else(|| {
+            anyhow!(
+                "{} does not hold a transfer from the sender {}",
+                path.display(),
+                self.sender
+            )
+        })?;
+        tx.verify().map_err(|e| {
+            anyhow!(
+                "signed transaction does not verify (not signed by the sender?): {}",
+                e
+            )
+        })?;
+        // A sweep number already recorded means the transaction was broadcast before, or built
+        // before a later sweep went out
+        if let Some(sequence) = sequence.filter(|s| *s <= self.state.transfer_sequence) {
+            return Err(anyhow!(
+                "signed transaction is sweep #{}, but sweep #{} is already recorded; refusing to broadcast it",
+                sequence,
+                self.state.transfer_sequence
+            ));
+        }
+        let sequence = sequence.unwrap_or(self.state.transfer_sequence + 1);
+
+        // The receiver was fixed when the transaction was built; hold it to the same checks as
+        // a sweep before anything is sent
+        let cycle = Cycle::new(info_span!("broadcast", signature = field::Empty));
+        match self.validate_receiver(&cycle, &receiver) {
+            ReceiverCheck::Valid => {}
+            ReceiverCheck::Unknown => {
+                return Err(anyhow!(
+                    "could not validate the receiver {}; try again later",
+                    receiver
+                ));
+            }
+            ReceiverCheck::Refused(reason) => {
+                let msg = format!(
+                    "CRITICAL: broadcast of {} SOL signed offline aborted, receiver {} {}",
+                    amount::sol(amount),
+                    receiver,
+                    reason
+                );
+                self.notifier
+                    .notify(notifier::Kind::ReceiverRefused, &msg)
+                    .await;
+                return Err(anyhow!("receiver {} {}", receiver, reason));
+            }
+        }
+        if let Some(max) = self.receiver_max_expected_lamports {
+            let receiver_balance =
+                traced_rpc!(cycle, "getBalance", self.rpc.get_balance(&receiver))
+                    .context("getting receiver balance")?;
+            if receiver_balance > max {
+                let msg = format!(
+                    "Receiver {} balance unexpectedly high ({} SOL > {} SOL expected), possible misconfig; broadcast of {} SOL signed offline aborted",
+                    receiver,
+                    amount::sol(receiver_balance),
+                    amount::sol(max),
+                    amount::sol(amount)
+                );
+                self.notifier
+                    .notify(notifier::Kind::ReceiverBalanceHigh, &msg)
+                    .await;
+                return Err(anyhow!(
+                    "receiver {} balance {} SOL exceeds receiver_max_expected_lamports ({} SOL); refusing to broadcast",
+                    receiver,
+                    amount::sol(receiver_balance),
+                    amount::sol(max)
+                ));
+            }
+        }
+
+        let sig = self
+            .send_and_confirm(&cycle, &tx, None)
+            .map_err(|e| anyhow!("broadcasting signed transaction: {}", e))?;
+        self.record_transfer(&cycle, &sig, &receiver, amount, None, sequence)
+            .await;
+        self.record_sweep_time();
+        let msg = format!(
+            "Transfer of {} from {} to {} (sweep #{}, signed offline) confirmed. Signature: {}",
+            amount::exact(amount),
+            self.sender,
+            receiver,
+            sequence,
+            sig
+        );
+        self.notifier.notify(notifier::Kind::Transfer, &msg).await;
+        println!("{}", msg);
+        Ok(())
+    }
+
+    /// Settle the transfer left in flight by a submission failure, if any, and report whether a
+    /// new transfer may go out. One that landed after all is recorded like any confirmed
+    /// transfer, and the cycle is deferred so the next one starts from a balance reflecting it.
+    async fn settle_in_flight(&mut self, cycle: &Cycle) -> bool {
+        let Some(pending) = self.in_flight else {
+            return true;
+        };
+        #[allow(clippy::result_large_err)] // ClientError, as returned by RpcClient itself
+        let status = |daemon: &Self| {
+            traced_rpc!(
+                cycle,
+                "getSignatureStatuses",
+                daemon
+                    .confirm_rpc()
+                    .get_signature_status(&pending.signature)
+            )
+        };
+        let mut result = status(self);
+        if let Ok(None) = result {
+            match traced_rpc!(
+                cycle,
+                "isBlockhashValid",
+                self.rpc
+                    .is_blockhash_valid(&pending.blockhash, CommitmentConfig::finalized())
+            ) {
+                Ok(true) => {
+                    warn!(
+                        "Previous transaction may still land; holding transfers: signature = {}",
+                        redact::addr(pending.signature)
+                    );
+                    return false;
+                }
+                // It may have been finalized in its last valid block in the meantime
+                Ok(false) => result = status(self),
+                Err(e) => result = Err(e),
+            }
+        }
+        match result {
+            Ok(Some(Ok(()))) => {
+                warn!(
+                    "Previous transaction landed after all: signature = {}, sequence = {}",
+                    redact::addr(pending.signature),
+                    pending.sequence
+                );
+                self.in_flight = None;
+                self.failure_streak = None;
+                self.record_transfer(
+                    cycle,
+                    &pending.signature,
+                    &pending.receiver,
+                    pending.amount,
+                    None,
+                    pending.sequence,
+                )
+                .await;
+                self.record_sweep_time();
+                let msg = format!(
+                    "Transfer of {} from {} to {} (sweep #{}) landed after all. Signature: {}",
+                    amount::exact(pending.amount),
+                    self.sender,
+                    pending.receiver,
+                    pending.sequence,
+                    pending.signature
+                );
+                self.notifier.notify(notifier::Kind::Transfer, &msg).await;
+                self.last_transfer_at = Instant::now();
+                false
+            }
+            Ok(Some(Err(e))) => {
+                info!(
+                    "Previous transaction failed on chain; nothing was moved: signature = {}, error = {}",
+                    redact::addr(pending.signature),
+                    e
+                );
+                self.in_flight = None;
+                true
+            }
+            Ok(None) => {
+                info!(
+                    "Previous transaction expired without landing: signature = {}",
+                    redact::addr(pending.signature)
+                );
+                self.in_flight = None;
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to check previous transaction; holding transfers: signature = {}, error = {}",
+                    redact::addr(pending.signature),
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Count consecutive identical transfer failures; on reaching `repeated_failure_limit`, send a
+    /// critical alert and, with `halt_on_repeated_failure`, suspend transfers.
+    async fn record_transfer_failure(&mut self, signature: String) {
+        let count = match &mut self.failure_streak {
+            Some((last, count)) if *last == signature => {
+                *count += 1;
+                *count
+            }
+            _ => {
+                self.failure_streak = Some((signature.clone(), 1));
+                1
+            }
+        };
+        if self.repeated_failure_limit != Some(count) {
+            return;
+        }
+        self.halted = self.halt_on_repeated_failure;
+        error!(
+            "Transfer failed repeatedly with the same error: failures = {}, halted = {}, error = {}",
+            count, self.halted, signature
+        );
+        let msg = format!(
+            "CRITICAL: transfer from {} failed {} times in a row with the same error: {}. {}",
+            self.sender,
+            count,
+            signature,
+            if self.halted {
+                "Transfers are halted until the daemon is restarted."
+            } else {
+                "It will keep being retried every cycle."
+            }
+        );
+        self.notifier
+            .notify(notifier::Kind::RepeatedFailure, &msg)
+            .await;
+    }
+
+    /// `migrate-receiver`: without `confirm`, send a test transfer of `test_lamports` to `new` and
+    /// remember it as pending; with `confirm`, persist the pending receiver as the override used
+    /// from the next start on. Needs a state file.
+    pub fn migrate_receiver(
+        &mut self,
+        new: Pubkey,
+        confirm: bool,
+        test_lamports: u64,
+    ) -> Result<()> {
+        let state_path = self.state_path.clone().ok_or_else(|| {
+            anyhow!("migrate-receiver requires state_file to be set in the config")
+        })?;
+        if !self.receiver_allowlist.is_empty() && !self.receiver_allowlist.contains(&new) {
+            return Err(anyhow!("new receiver {} is not in receiver_allowlist", new));
+        }
+
+        if confirm {
+            if self.state.pending_receiver.as_deref() != Some(new.to_string().as_str()) {
+                return Err(anyhow!(
+                    "no confirmed test transfer to {}; run `migrate-receiver --new {}` first",
+                    new,
+                    new
+                ));
+            }
+            self.state.receiver_override = Some(new.to_string());
+            self.state.pending_receiver = None;
+            self.state.save(&state_path)?;
+            println!(
+                "Receiver override set to {}; restart the daemon to sweep to it",
+                new
+            );
+            return Ok(());
+        }
+
+        let cycle = Cycle::new(info_span!("migrate_receiver", signature = field::Empty));
+        let (sig, _, _) = self
+            .execute_transfer(&cycle, &new, test_lamports, None)
+            .map_err(|_| anyhow!("test transfer to {} failed; see the log", new))?;
+        self.state.pending_receiver = Some(new.to_string());
+        self.state.save(&state_path)?;
+        println!(
+            "Test transfer of {} to {} confirmed. Signature: {}",
+            amount::exact(test_lamports),
+            new,
+            sig
+        );
+        println!(
+            "Verify it arrived, then run `migrate-receiver --new {} --confirm`",
+            new
+        );
+        Ok(())
+    }
+
+    /// Fetch the receipt of a confirmed transfer to `receiver`, retrying while the RPC node does
+    /// not serve it yet. `None` (logged) if it is still unavailable after `RECEIPT_ATTEMPTS`.
+    async fn fetch_receipt(
+        &self,
+        cycle: &Cycle,
+        sig: &Signature,
+        receiver: &Pubkey,
+    ) -> Option<Receipt> {
+        for attempt in 1..=RECEIPT_ATTEMPTS {
+            if cycle.out_of_budget("the transaction receipt was fetched") {
+                return None;
+            }
+            match traced_rpc!(
+                cycle,
+                "getTransaction",
+                self.rpc.get_transaction(sig, UiTransactionEncoding::Json)
+            ) {
+                Ok(tx) => {
+                    let Some(meta) = tx.transaction.meta else {
+                        warn!(
+                            "Transaction receipt has no status meta: signature = {}",
+                            redact::addr(sig)
+                        );
+                        return None;
+                    };
+                    let account_keys = match tx.transaction.transaction {
+                        EncodedTransaction::Json(ui) => match ui.message {
+                            UiMessage::Raw(raw) => raw.account_keys,
+                            UiMessage::Parsed(parsed) => {
+                                parsed.account_keys.into_iter().map(|k| k.pubkey).collect()
+                            }
+                        },
+                        _ => Vec::new(),
+                    };
+                    let balances = |key: &Pubkey| {
+                        let key = key.to_string();
+                        let i = account_keys.iter().position(|k| *k == key)?;
+                        Some((*meta.pre_balances.get(i)?, *meta.post_balances.get(i)?))
+                    };
+                    return Some(Receipt {
+                        slot: tx.slot,
+                        fee_lamports: meta.fee,
+                        sender_balances: balances(&self.sender),
+                        receiver_balances: balances(receiver),
+                    });
+                }
+                Err(e) if attempt < RECEIPT_ATTEMPTS => {
+                    info!(
+                        "Transaction receipt not available yet; retrying: attempt = {}, error = {}",
+                        attempt, e
+                    );
+                    sleep(RECEIPT_RETRY_DELAY).await;
+                }
+                Err(e) => {
+                    warn!(
+                        "Giving up on transaction receipt: signature = {}, error = {}",
+                        redact::addr(sig),
+                        e
+                    );
+                }
+            }
+        }
+        None
+    }
+
+    /// Idle for too long: prove the signer and RPC path still work with a memo-only tx.
+    async fn send_keepalive(&mut self, cycle: &Cycle) -> CycleOutcome {
+        let ix = memo_instruction(&self.sender, KEEPALIVE_MEMO);
+        let (recent_blockhash, last_valid_block_height) = match self.latest_blockhash(cycle) {
+            Ok(h) => h,
+            Err(e) => {
+                error!("Failed to get recent blockhash for keep-alive: {}", e);
+                return CycleOutcome::Deferred;
+            }
+        };
+        let tx = match self.signed_transaction(&[ix], recent_blockhash) {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Failed to sign keep-alive memo: {:#}", e);
+                return CycleOutcome::Deferred;
+            }
+        };
+        let fee_lamports = traced_rpc!(
+            cycle,
+            "getFeeForMessage",
+            self.rpc.get_fee_for_message(&tx.message)
+        )
+        .unwrap_or(0);
+        match self.send_and_confirm(cycle, &tx, last_valid_block_height) {
+            Ok(sig) => {
+                info!(
+                    "Keep-alive memo confirmed: signature = {}, fee_lamports = {}",
+                    redact::addr(sig),
+                    fee_lamports
+                );
+                let msg = format!(
+                    "Keep-alive memo sent from {} (no transfer for {}s). Fee: {} Lamports. Signature: {}",
+                    self.sender,
+                    self.last_transfer_at.elapsed().as_secs(),
+                    fee_lamports,
+                    sig
+                );
+                self.notifier.notify(notifier::Kind::KeepAlive, &msg).await;
+                self.last_transfer_at = Instant::now();
+                CycleOutcome::KeepAlive(sig)
+            }
+            Err(e) => {
+                error!("Keep-alive memo failed: {}", e);
+                let msg = format!("Keep-alive memo from {} failed: {}", self.sender, e);
+                self.notifier
+                    .notify(notifier::Kind::KeepAliveFailed, &msg)
+                    .await;
+                CycleOutcome::Deferred
+            }
+        }
+    }
+
+    /// Blockhash to sign with. With `finalized_blockhash` it is fetched at finalized commitment
+    /// together with its last valid block height, which then bounds the confirmation wait.
+    /// With `min_blockhash_validity_blocks`, one too close to expiry is refetched.
+    #[allow(clippy::result_large_err)] // ClientError, as returned by RpcClient itself
+    fn latest_blockhash(&self, cycle: &Cycle) -> Result<(Hash, Option<u64>), ClientError> {
+        if let Some(min_validity) = self.min_blockhash_validity {
+            let commitment = if self.finalized_blockhash {
+                CommitmentConfig::finalized()
+            } else {
+                self.rpc.commitment()
+            };
+            for attempt in 1..=MAX_BLOCKHASH_FETCHES {
+                let (hash, last_valid) = traced_rpc!(
+                    cycle,
+                    "getLatestBlockhash",
+                    self.rpc.get_latest_blockhash_with_commitment(commitment)
+                )?;
+                // The node's most recent block, which the transaction must land within range of
+                let height = traced_rpc!(
+                    cycle,
+                    "getBlockHeight",
+                    self.rpc
+                        .get_block_height_with_commitment(CommitmentConfig::processed())
+                )?;
+                let remaining = last_valid.saturating_sub(height);
+                if remaining >= min_validity {
+                    info!(
+                        "Blockhash validity window: remaining_blocks = {}, last_valid_block_height = {}",
+                        remaining, last_valid
+                    );
+                    return Ok((hash, self.finalized_blockhash.then_some(last_valid)));
+                }
+                warn!(
+                    "Blockhash too close to expiry; refetching: remaining_blocks = {}, min_blockhash_validity_blocks = {}, attempt = {}",
+                    remaining, min_validity, attempt
+                );
+                if attempt < MAX_BLOCKHASH_FETCHES {
+                    std::thread::sleep(BLOCKHASH_REFETCH_DELAY);
+                }
+            }
+            return Err(ClientErrorKind::Custom(format!(
+                "RPC node keeps returning blockhashes with fewer than {} blocks of validity left; it may be lagging",
+                min_validity
+            ))
+            .into());
+        }
+        if self.finalized_blockhash {
+            traced_rpc!(
+                cycle,
+                "getLatestBlockhash",
+                self.rpc
+                    .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+            )
+            .map(|(hash, last_valid)| (hash, Some(last_valid)))
+        } else {
+            traced_rpc!(cycle, "getLatestBlockhash", self.rpc.get_latest_blockhash())
+                .map(|hash| (hash, None))
+        }
+    }
+
+    /// Send `tx` and wait for it to confirm, polling `confirm_rpc_provider` if set. With a
+    /// `last_valid_block_height` the wait ends once the finalized block height passes it (without
+    /// one, once the blockhash is no longer valid), since the transaction can no longer land;
+    /// that is reported as `BlockhashNotFound` so the caller re-signs and resends. The wait also
+    /// ends with the cycle budget, leaving the transaction's outcome unknown.
+    #[allow(clippy::result_large_err)]
+    fn send_and_confirm(
+        &self,
+        cycle: &Cycle,
+        tx: &Transaction,
+        last_valid_block_height: Option<u64>,
+    ) -> Result<Signature, ClientError> {
+        if last_valid_block_height.is_none()
+            && self.confirm_rpc.is_none()
+            && cycle.deadline.is_none()
+        {
+            return traced_rpc!(
+                cycle,
+                "sendAndConfirmTransaction",
+                self.rpc.send_and_confirm_transaction(tx)
+            );
+        }
+        let confirm_rpc = self.confirm_rpc();
+        let sig = traced_rpc!(cycle, "sendTransaction", self.rpc.send_transaction(tx))?;
+        loop {
+            let status = traced_rpc!(
+                cycle,
+                "getSignatureStatuses",
+                confirm_rpc.get_signature_status(&sig)
+            )?;
+            if let Some(result) = status {
+                return result.map(|()| sig).map_err(Into::into);
+            }
+            let expired = match last_valid_block_height {
+                Some(last_valid) => {
+                    let height =
+                        traced_rpc!(cycle, "getBlockHeight", confirm_rpc.get_block_height())?;
+                    (height > last_valid).then(|| {
+                        format!(
+                            "block_height = {}, last_valid_block_height = {}",
+                            height, last_valid
+                        )
+                    })
+                }
+                None => (!traced_rpc!(
+                    cycle,
+                    "isBlockhashValid",
+                    confirm_rpc.is_blockhash_valid(
+                        &tx.message.recent_blockhash,
+                        CommitmentConfig::finalized()
+                    )
+                )?)
+                .then(|| format!("blockhash = {}", tx.message.recent_blockhash)),
+            };
+            if let Some(expired) = expired {
+                // It may still have landed in the last valid block
+                if let Some(result) = traced_rpc!(
+                    cycle,
+                    "getSignatureStatuses",
+                    confirm_rpc.get_signature_status(&sig)
+                )? {
+                    return result.map(|()| sig).map_err(Into::into);
+                }
+                warn!(
+                    "The transaction's validity window passed; it can no longer land: signature = {}, {}",
+                    redact::addr(sig),
+                    expired
+                );
+                return Err(TransactionError::BlockhashNotFound.into());
+            }
+            if cycle.out_of_budget("the transaction confirmed") {
+                return Err(ClientErrorKind::Custom(format!(
+                    "cycle budget exhausted while confirming {}",
+                    sig
+                ))
+                .into());
+            }
+            std::thread::sleep(
+                CONFIRM_POLL_INTERVAL.min(cycle.remaining().unwrap_or(Duration::MAX)),
+            );
+        }
+    }
+
+    /// Build a transaction paid for by the fee payer (or the sender) and signed by both. Fails
+    /// while the sender keypair is kept offline.
+    fn signed_transaction(
+        &self,
+        instructions: &[Instruction],
+        recent_blockhash: Hash,
+    ) -> Result<Transaction> {
+        let keypair = self.keypair.as_ref().ok_or_else(|| {
+            anyhow!("the sender keypair is kept offline; nothing can be signed on this host")
+        })?;
+        let mut signers: Vec<&dyn Signer> = vec![keypair];
+        let payer = match &self.fee_payer {
+            Some(fee_payer) => {
+                signers.push(fee_payer);
+                fee_payer.pubkey()
+            }
+            None => self.sender,
+        };
+        let mut tx = Transaction::new_with_payer(instructions, Some(&payer));
+        tx.sign(&signers, recent_blockhash);
+        Ok(tx)
+    }
+}
+
+/// Persist the state file if one is configured; failures are only logged.
+fn save_state(state: &State, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(e) = state.save(path) {
+            error!("Failed to save state: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LAMPORTS_PER_SOL;
+    use serde_json::Value;
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+    use solana_rpc_client::mock_sender::MockSender;
+
+    fn mock_rpc(url: &str, mocks: HashMap<RpcRequest, Value>) -> RpcClient {
+        RpcClient::new_mock_with_mocks(url.to_string(), mocks)
+    }
+
+    fn mock_response<T: serde::Serialize>(value: T) -> Value {
+        json!(Response {
+            context: RpcResponseContext {
+                slot: 1,
+                api_version: None
+            },
+            value,
+        })
+    }
+
+    fn signed_transfer() -> Transaction {
+        let payer = Keypair::new();
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let mut tx = Transaction::new_with_payer(&[ix], Some(&payer.pubkey()));
+        tx.sign(&[&payer], Hash::new_unique());
+        tx
+    }
+
+    /// A daemon with a 7 SOL threshold and every optional feature off.
+    fn test_daemon(rpc: RpcClient) -> Daemon {
+        let keypair = Keypair::new();
+        let started_at = Instant::now();
+        Daemon {
+            rpc,
+            confirm_rpc: None,
+            expected_genesis_hash: None,
+            sender: keypair.pubkey(),
+            keypair: Some(keypair),
+            fee_payer: None,
+            receiver: Pubkey::new_unique(),
+            receiver_allowlist: Vec::new(),
+            fallback_receiver: None,
+            allow_vote_account_receiver: false,
+            amount_bands: Vec::new(),
+            tiers: Vec::new(),
+            receiver_windows: Vec::new(),
+            threshold_lamports: 7 * LAMPORTS_PER_SOL,
+            trigger_lamports: 7 * LAMPORTS_PER_SOL,
+            trigger_relative_percent: 0.0,
+            config_account: None,
+            on_chain_config: None,
+            near_threshold_margin_lamports: None,
+            monitored_stake_accounts: Vec::new(),
+            deactivating_stake_min_lamports: 0,
+            deactivation_reserve_lamports: 0,
+            obligations: Vec::new(),
+            obligation_horizon: Duration::from_secs(30 * SECONDS_PER_DAY),
+            reserve_rent_exempt: false,
+            rent_exempt_minimum: None,
+            receiver_target_lamports: None,
+            receiver_max_expected_lamports: None,
+            first_transfer_max_lamports: None,
+            chunk_lamports: None,
+            approval: None,
+            poll_interval: Duration::from_secs(1),
+            adaptive_polling: None,
+            next_poll_interval: Duration::from_secs(1),
+            catch_up_overruns: false,
+            last_balance: None,
+            recent_balances: Vec::new(),
+            notification_balance_history: 0,
+            accrual_rate: None,
+            cycle_budget: None,
+            rpc_deadline: Deadline::default(),
+            balance_commitment: CommitmentConfig::finalized(),
+            finalized_recheck: false,
+            finalized_blockhash: false,
+            min_blockhash_validity: None,
+            max_send_attempts: DEFAULT_MAX_SEND_ATTEMPTS,
+            priority_fee: None,
+            repeated_failure_limit: None,
+            halt_on_repeated_failure: false,
+            failure_streak: None,
+            halted: false,
+            max_transfers_per_hour: None,
+            halt_on_excessive_transfers: false,
+            recent_transfers: VecDeque::new(),
+            transfer_rate_alerted: false,
+            dry_spell_alert_multiple: None,
+            dry_spell_alerted: false,
+            in_flight: None,
+            quorum_rpcs: Vec::new(),
+            unconfirmed_balance: None,
+            quorum_balance_reads: 1,
+            quorum_tolerance_lamports: 0,
+            min_uptime: Duration::ZERO,
+            pause_file: None,
+            failover: None,
+            failover_active: None,
+            pause_when_delinquent: false,
+            allowed_weekdays: Vec::new(),
+            alert_on_zero_balance: true,
+            last_nonzero_balance: None,
+            sweep_per_epoch: false,
+            sweep_epoch_slot_offset: 0,
+            sweep_delay: None,
+            excess_since: None,
+            keepalive_interval: None,
+            history_file: None,
+            dry_run: false,
+            dry_run_notify: false,
+            profile: false,
+            history_rotation: None,
+            audit_sign: false,
+            state_path: None,
+            state: State::default(),
+            notifier: Notifier::new(None, false, Duration::from_secs(1), None, None, "test"),
+            metrics: Metrics::new("test").unwrap(),
+            started_at,
+            last_transfer_at: started_at,
+            fee_report_interval: None,
+            fee_report_max_fee_fraction: DEFAULT_FEE_REPORT_MAX_FEE_FRACTION,
+            last_fee_report: None,
+            unsigned_output: None,
+            observe_only: false,
+            balance_stream: None,
+        }
+    }
+
+    #[test]
+    fn expired_blockhash_is_classified_for_resend() {
+        // Signature never found and the blockhash is no longer valid: the transaction expired
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::IsBlockhashValid, mock_response(false));
+        let rpc = mock_rpc("sig_not_found", mocks);
+        let err = rpc
+            .send_and_confirm_transaction(&signed_transfer())
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::BlockhashExpired);
+    }
+
+    #[test]
+    fn blockhash_not_found_is_classified_for_resend() {
+        let err = ClientError::from(TransactionError::BlockhashNotFound);
+        assert_eq!(classify_send_error(&err), SendFailure::BlockhashExpired);
+    }
+
+    #[test]
+    fn transaction_failure_is_a_submission_failure() {
+        let rpc = mock_rpc("instruction_error", HashMap::new());
+        let err = rpc
+            .send_and_confirm_transaction(&signed_transfer())
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::Submission);
+
+        let rpc = mock_rpc("account_in_use", HashMap::new());
+        let err = rpc
+            .send_and_confirm_transaction(&signed_transfer())
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::Submission);
+    }
+
+    #[test]
+    fn preflight_failure_is_told_apart_from_submission() {
+        // As the RPC client reports a transaction the node refused in simulation
+        let err = ClientError::from(RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed: Attempt to debit an account but found no record of a prior credit.".to_string(),
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(
+                RpcSimulateTransactionResult {
+                    err: Some(TransactionError::AccountNotFound),
+                    logs: Some(Vec::new()),
+                    accounts: None,
+                    units_consumed: None,
+                    loaded_accounts_data_size: None,
+                    return_data: None,
+                    inner_instructions: None,
+                    replacement_blockhash: None,
+                },
+            ),
+        });
+        assert_eq!(classify_send_error(&err), SendFailure::Preflight);
+
+        // A node that accepted the transaction but never answered with a signature
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::SendTransaction, Value::Null);
+        let err = mock_rpc("succeeds", mocks)
+            .send_and_confirm_transaction(&signed_transfer())
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::Submission);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn submission_failure_holds_transfers_until_settled() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("instruction_error", mocks.clone()));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        let pending = daemon.in_flight.expect("left in flight");
+        assert_eq!(pending.amount, 2 * LAMPORTS_PER_SOL);
+
+        // Neither landed nor expired: the next cycle sends nothing
+        mocks.insert(RpcRequest::IsBlockhashValid, mock_response(true));
+        daemon.rpc = mock_rpc("sig_not_found", mocks.clone());
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.in_flight, Some(pending));
+
+        // It landed after all: recorded once, and the cycle starts over from a fresh balance
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.in_flight, None);
+        assert_eq!(daemon.metrics.transfers_total.get(), 1);
+        assert_eq!(daemon.state.transfer_sequence, pending.sequence);
+    }
+
+    #[test]
+    fn finalized_blockhash_confirms_within_validity_window() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.finalized_blockhash = true;
+        let cycle = Cycle::new(Span::none());
+        let receiver = daemon.receiver;
+        assert!(daemon.execute_transfer(&cycle, &receiver, 1, None).is_ok());
+    }
+
+    #[test]
+    fn blockhash_close_to_expiry_is_refetched_then_refused() {
+        // The mock's blockhash is valid until block 1234
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBlockHeight, json!(1200));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.min_blockhash_validity = Some(30);
+        let cycle = Cycle::new(Span::none());
+        assert!(daemon.latest_blockhash(&cycle).is_ok());
+
+        // Later fetches find the blockhash at its last valid block
+        let err = daemon.latest_blockhash(&cycle).unwrap_err();
+        assert!(err.to_string().contains("may be lagging"), "{}", err);
+        let phases = cycle.phases.lock().unwrap();
+        let fetches = phases.iter().filter(|(m, _)| *m == "getLatestBlockhash");
+        assert_eq!(fetches.count(), 1 + MAX_BLOCKHASH_FETCHES as usize);
+    }
+
+    #[test]
+    fn confirmation_ends_once_validity_window_passes() {
+        // Signature never found and the block height is one past the last valid one
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBlockHeight, json!(1235));
+        let daemon = test_daemon(mock_rpc("sig_not_found", mocks));
+        let cycle = Cycle::new(Span::none());
+        let err = daemon
+            .send_and_confirm(&cycle, &signed_transfer(), Some(1234))
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::BlockhashExpired);
+        // The last status check before giving up is profiled like the others
+        let phases: Vec<_> = cycle
+            .phases
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(m, _)| *m)
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                "sendTransaction",
+                "getSignatureStatuses",
+                "getBlockHeight",
+                "getSignatureStatuses"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keepalive_rpc_calls_are_profiled() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let cycle = Cycle::new(Span::none());
+        assert!(matches!(
+            daemon.send_keepalive(&cycle).await,
+            CycleOutcome::KeepAlive(_)
+        ));
+        let phases = cycle.phases.lock().unwrap();
+        assert!(phases.iter().any(|(m, _)| *m == "getFeeForMessage"));
+    }
+
+    #[test]
+    fn confirmation_ends_with_the_cycle_budget() {
+        // Signature never found, still within its validity window
+        let daemon = test_daemon(mock_rpc("sig_not_found", HashMap::new()));
+        let cycle = Cycle::new(Span::none()).with_budget(Some(Duration::from_secs(1)));
+        let err = daemon
+            .send_and_confirm(&cycle, &signed_transfer(), Some(1234))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cycle budget exhausted"),
+            "{}",
+            err
+        );
+        assert_eq!(classify_send_error(&err), SendFailure::Submission);
+        assert!(cycle.started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn confirmation_polls_the_confirm_rpc() {
+        // The send endpoint would confirm at once; the confirm endpoint never finds the signature
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBlockHeight, json!(1235));
+        daemon.confirm_rpc = Some(mock_rpc("sig_not_found", mocks));
+        let cycle = Cycle::new(Span::none());
+        let err = daemon
+            .send_and_confirm(&cycle, &signed_transfer(), Some(1234))
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::BlockhashExpired);
+
+        // Without a last valid block height the confirm endpoint's blockhash check bounds the wait
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::IsBlockhashValid, mock_response(false));
+        daemon.confirm_rpc = Some(mock_rpc("sig_not_found", mocks));
+        let err = daemon
+            .send_and_confirm(&cycle, &signed_transfer(), None)
+            .unwrap_err();
+        assert_eq!(classify_send_error(&err), SendFailure::BlockhashExpired);
+
+        daemon.confirm_rpc = Some(mock_rpc("succeeds", HashMap::new()));
+        assert!(
+            daemon
+                .send_and_confirm(&cycle, &signed_transfer(), None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn balance_quorum_requires_agreeing_endpoints() {
+        // Mock endpoints report 50 lamports
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.quorum_rpcs = vec![
+            ("a".to_string(), mock_rpc("succeeds", HashMap::new())),
+            ("b".to_string(), mock_rpc("fails", HashMap::new())),
+        ];
+        daemon.quorum_balance_reads = 2;
+        daemon.quorum_tolerance_lamports = 10;
+        let cycle = Cycle::new(Span::none());
+        assert!(daemon.balance_quorum_agrees(&cycle, 55));
+        assert!(!daemon.balance_quorum_agrees(&cycle, 5 * LAMPORTS_PER_SOL));
+        daemon.quorum_balance_reads = 3;
+        assert!(!daemon.balance_quorum_agrees(&cycle, 50));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn repeated_identical_failures_halt_transfers() {
+        let mut daemon = test_daemon(mock_rpc("instruction_error", HashMap::new()));
+        daemon.repeated_failure_limit = Some(2);
+        daemon.halt_on_repeated_failure = true;
+        let cycle = Cycle::new(Span::none());
+
+        // A different error in between restarts the count
+        daemon.record_transfer_failure("other".to_string()).await;
+        for _ in 0..2 {
+            assert!(!daemon.halted);
+            assert_eq!(
+                daemon
+                    .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+                    .await,
+                CycleOutcome::Deferred
+            );
+        }
+        assert!(daemon.halted);
+        // Halted: no further attempt is made
+        daemon
+            .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+            .await;
+        assert_eq!(daemon.failure_streak.as_ref().map(|(_, n)| *n), Some(2));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn priority_fee_adjusts_to_the_realized_confirmation_time() {
+        // Sent at processed slot 0 and landed in slot 2 (the mock receipt)
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.priority_fee = Some(PriorityFee::new(1, DEFAULT_MAX_COMPUTE_UNIT_PRICE));
+        let cycle = Cycle::new(Span::none());
+        assert!(matches!(
+            daemon
+                .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+                .await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert_eq!(daemon.priority_fee.as_ref().unwrap().adjustment, 1.5);
+
+        // Sent at slot 2: confirmed within the target
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetSlot, json!(2));
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        daemon
+            .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+            .await;
+        assert!((daemon.priority_fee.as_ref().unwrap().adjustment - 1.35).abs() < 1e-9);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn observer_exports_metrics_but_never_transfers() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.observe_only = true;
+        daemon.keypair = None;
+        daemon.keepalive_interval = Some(Duration::ZERO);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.state.transfer_sequence, 0);
+        assert_eq!(daemon.metrics.excess_sol.get(), 2.0);
+        // At or below the threshold no keep-alive memo goes out either
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn offline_keypair_skips_keepalive_and_refuses_to_sign() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(7 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.keypair = None;
+        daemon.keepalive_interval = Some(Duration::ZERO);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+        let ix = memo_instruction(&daemon.sender, KEEPALIVE_MEMO);
+        assert!(
+            daemon
+                .signed_transaction(&[ix], Hash::new_unique())
+                .is_err()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn too_many_transfers_in_an_hour_alert_once_and_halt() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.max_transfers_per_hour = Some(2);
+        daemon.halt_on_excessive_transfers = true;
+        let cycle = Cycle::new(Span::none());
+
+        for _ in 0..2 {
+            assert!(matches!(
+                daemon
+                    .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+                    .await,
+                CycleOutcome::Transferred(_)
+            ));
+            assert!(!daemon.transfer_rate_alerted);
+        }
+        daemon
+            .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+            .await;
+        assert!(daemon.transfer_rate_alerted);
+        assert!(daemon.halted);
+        // Halted: no further transfer is counted
+        daemon
+            .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+            .await;
+        assert_eq!(daemon.recent_transfers.len(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_dry_spell_far_beyond_the_usual_interval_is_alerted_once() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.dry_spell_alert_multiple = Some(3.0);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        // Sweeps every 1000s, the last one 2000s ago
+        daemon.state.transfer_times = vec![now - 4_000, now - 3_000, now - 2_000];
+        daemon.check_dry_spell().await;
+        assert!(!daemon.dry_spell_alerted);
+
+        daemon.state.transfer_times = vec![now - 6_000, now - 5_000, now - 4_000];
+        daemon.check_dry_spell().await;
+        assert!(daemon.dry_spell_alerted);
+
+        // A sweep ends the dry spell
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert!(!daemon.dry_spell_alerted);
+        assert_eq!(daemon.state.transfer_times.len(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfer_that_keeps_expiring_is_given_up_and_recorded_as_failed() {
+        // Signature never found and the block height past the blockhash's last valid block
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBlockHeight, json!(1235));
+        let mut daemon = test_daemon(mock_rpc("sig_not_found", mocks));
+        daemon.finalized_blockhash = true;
+        daemon.max_send_attempts = 1;
+        let path = std::env::temp_dir().join(format!("aft-expiry-test-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        daemon.history_file = Some(path.clone());
+        let cycle = Cycle::new(Span::none());
+
+        assert_eq!(
+            daemon
+                .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+                .await,
+            CycleOutcome::Deferred
+        );
+        let entries = history::read_all(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].lamports, LAMPORTS_PER_SOL);
+        assert_eq!(entries[0].sequence, None);
+        assert!(
+            entries[0]
+                .failure
+                .as_deref()
+                .is_some_and(|f| f.starts_with("failed to land after 1 attempts")),
+            "{:?}",
+            entries[0].failure
+        );
+        // The sweep number is reused by the retry
+        assert_eq!(daemon.state.transfer_sequence, 0);
+        assert!(daemon.failure_streak.is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn off_curve_receivers_are_told_apart_from_wallets() {
+        let wallet = Keypair::new().pubkey();
+        assert_eq!(
+            receiver_kind(&wallet, Some(solana_system_interface::program::ID)),
+            "on-curve (wallet address), system-owned"
+        );
+        let (pda, _) = Pubkey::find_program_address(&[b"treasury"], &VOTE_PROGRAM_ID);
+        assert_eq!(
+            receiver_kind(&pda, None),
+            "off-curve (program-derived address, no private key), no account yet; the first transfer creates it as a system account and must cover its rent-exempt minimum"
+        );
+        assert!(
+            receiver_kind(&pda, Some(MEMO_PROGRAM_ID))
+                .ends_with("only that program can move SOL out of it")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unsigned_transfer_is_built_then_broadcast_once_signed() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let path = std::env::temp_dir().join(format!("aft-unsigned-{}.txt", std::process::id()));
+        daemon.set_build_unsigned(path.display().to_string());
+        let cycle = Cycle::new(Span::none());
+        assert_eq!(
+            daemon
+                .sweep_excess(&cycle, 0, LAMPORTS_PER_SOL, daemon.receiver)
+                .await,
+            CycleOutcome::WouldTransfer(LAMPORTS_PER_SOL)
+        );
+        let mut tx = decode_transaction(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(tx.verify().is_err());
+        assert_eq!(
+            sweep_transfer(&tx, &daemon.sender),
+            Some((daemon.receiver, LAMPORTS_PER_SOL, Some(1)))
+        );
+
+        // A transfer not signed by the sender is refused before anything is sent
+        daemon.unsigned_output = None;
+        let err = daemon.broadcast(&path).await.unwrap_err();
+        assert!(err.to_string().contains("does not verify"), "{}", err);
+
+        let keypair = daemon.keypair.take().unwrap();
+        let blockhash = tx.message.recent_blockhash;
+        tx.sign(&[&keypair], blockhash);
+        fs::write(
+            &path,
+            BASE64_STANDARD.encode(bincode::serialize(&tx).unwrap()),
+        )
+        .unwrap();
+        // The receiver is checked like a sweep's before anything is sent
+        daemon.receiver_allowlist = vec![Keypair::new().pubkey()];
+        let err = daemon.broadcast(&path).await.unwrap_err();
+        assert!(err.to_string().contains("receiver_allowlist"), "{}", err);
+        daemon.receiver_allowlist = vec![daemon.receiver];
+        daemon.receiver_max_expected_lamports = Some(10);
+        let err = daemon.broadcast(&path).await.unwrap_err();
+        assert!(
+            err.to_string().contains("receiver_max_expected_lamports"),
+            "{}",
+            err
+        );
+        assert_eq!(daemon.state.transfer_sequence, 0);
+
+        // With audit_sign, the entry is marked as written without the keypair, not left unsigned
+        let history = path.with_extension("jsonl");
+        daemon.history_file = Some(history.clone());
+        daemon.audit_sign = true;
+        daemon.receiver_max_expected_lamports = None;
+        daemon.broadcast(&path).await.unwrap();
+        assert_eq!(daemon.state.transfer_sequence, 1);
+        let entries = history::read_all(&history).unwrap();
+        assert!(entries[0].signed_offline && entries[0].audit_signature.is_none());
+        history::verify_audit(&history, &daemon.sender).unwrap();
+        fs::remove_file(history).unwrap();
+
+        // Broadcasting it again would replay a recorded sweep
+        let err = daemon.broadcast(&path).await.unwrap_err();
+        assert!(err.to_string().contains("already recorded"), "{}", err);
+        assert_eq!(daemon.state.transfer_sequence, 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fee_payer_pays_and_both_keypairs_sign() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let fee_payer = Keypair::new();
+        let fee_payer_pubkey = fee_payer.pubkey();
+        daemon.fee_payer = Some(fee_payer);
+
+        let ix = system_instruction::transfer(&daemon.sender, &daemon.receiver, 1);
+        let tx = daemon
+            .signed_transaction(&[ix], Hash::new_unique())
+            .unwrap();
+        assert_eq!(tx.message.account_keys[0], fee_payer_pubkey);
+        assert_eq!(tx.message.header.num_required_signatures, 2);
+        assert!(tx.is_signed());
+        assert!(tx.verify().is_ok());
+    }
+
+    #[test]
+    fn adaptive_interval_follows_estimated_time_to_trigger() {
+        let bounds = AdaptivePolling {
+            min: Duration::from_secs(3600),
+            max: Duration::from_secs(36_000),
+        };
+        let trigger = 7 * LAMPORTS_PER_SOL;
+        // 1 SOL short at 0.0001 SOL/s: 10_000 s away
+        let rate = Some(100_000.0);
+        assert_eq!(
+            bounds.next_interval(6 * LAMPORTS_PER_SOL, trigger, rate),
+            Duration::from_secs(10_000)
+        );
+        // Nearly there: clamped to the minimum; far away: clamped to the maximum
+        assert_eq!(bounds.next_interval(trigger - 1, trigger, rate), bounds.min);
+        assert_eq!(bounds.next_interval(0, trigger, rate), bounds.max);
+        // Unknown rate or already above the trigger
+        assert_eq!(bounds.next_interval(0, trigger, None), bounds.max);
+        assert_eq!(bounds.next_interval(trigger + 1, trigger, None), bounds.min);
+    }
+
+    #[test]
+    fn overrunning_cycle_is_counted_and_optionally_caught_up() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.next_poll_interval = Duration::from_secs(100);
+        let interval = Duration::from_secs(100);
+
+        assert_eq!(
+            daemon.after_cycle(interval, Duration::from_secs(10)),
+            interval
+        );
+        assert_eq!(daemon.metrics.cycle_overrun_total.get(), 0);
+
+        assert_eq!(
+            daemon.after_cycle(interval, Duration::from_secs(130)),
+            interval
+        );
+        daemon.catch_up_overruns = true;
+        assert_eq!(
+            daemon.after_cycle(interval, Duration::from_secs(130)),
+            Duration::from_secs(70)
+        );
+        assert_eq!(daemon.metrics.cycle_overrun_total.get(), 2);
+    }
+
+    #[test]
+    fn rpc_version_is_parsed_from_solana_core() {
+        assert_eq!(
+            parse_rpc_version("2.3.11 (src:00000000; feat:1234, client:Agave)"),
+            Some((2, 3, 11))
+        );
+        assert_eq!(parse_rpc_version("1.18.26"), Some((1, 18, 26)));
+        assert_eq!(parse_rpc_version("1.9"), None);
+        assert_eq!(parse_rpc_version(""), None);
+        assert!(parse_rpc_version("1.8.16").unwrap() < MIN_RPC_VERSION);
+    }
+
+    #[test]
+    fn rpc_version_check_covers_enabled_features_only() {
+        assert!(check_rpc_version("1.8.16", false).is_err());
+        assert!(check_rpc_version("1.13.6", false).is_ok());
+        let err = check_rpc_version("1.13.6 (src:devbuild; feat:1365939126)", true).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("too old for target_confirmation_slots"),
+            "{}",
+            err
+        );
+        assert!(check_rpc_version("1.14.0", true).is_ok());
+        assert!(check_rpc_version("unknown", true).is_ok());
+    }
+
+    #[test]
+    fn banner_summarizes_the_effective_behavior() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        assert_eq!(
+            daemon.banner("daemon"),
+            format!(
+                "Mode: daemon | Threshold: {} SOL (static) | Poll: every 1s | Receivers: 1 | Notifications: none | Dry-run: no",
+                amount::sol(7 * LAMPORTS_PER_SOL)
+            )
+        );
+        daemon.fallback_receiver = Some(Pubkey::new_unique());
+        daemon.tiers = vec![(LAMPORTS_PER_SOL, daemon.receiver)];
+        daemon.reserve_rent_exempt = true;
+        daemon.set_dry_run(true);
+        let banner = daemon.banner("once");
+        assert!(banner.starts_with("Mode: once | "), "{}", banner);
+        assert!(banner.contains(" SOL (dynamic) | "), "{}", banner);
+        assert!(banner.contains(" | Receivers: 2 | "), "{}", banner);
+        assert!(banner.ends_with(" | Dry-run: yes"), "{}", banner);
+    }
+
+    #[test]
+    fn preflight_requires_a_healthy_rpc_node() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetHealth, json!("ok"));
+        assert!(test_daemon(mock_rpc("succeeds", mocks)).preflight().is_ok());
+
+        // Every call of the "fails" mock returns null
+        let err = test_daemon(mock_rpc("fails", HashMap::new()))
+            .preflight()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("getHealth"), "{}", err);
+    }
+
+    #[test]
+    fn cluster_check_refuses_a_different_cluster() {
+        let devnet = parse_expected_cluster("devnet").unwrap();
+        let mainnet = parse_expected_cluster("mainnet-beta").unwrap();
+        assert_eq!(parse_expected_cluster(&devnet.to_string()).unwrap(), devnet);
+        assert!(parse_expected_cluster("mainnet").is_err());
+
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetGenesisHash, json!(mainnet.to_string()));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks.clone()));
+        daemon.expected_genesis_hash = Some(devnet);
+        let err = daemon.check_cluster().unwrap_err().to_string();
+        assert!(err.contains("serves cluster mainnet-beta"), "{}", err);
+
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        daemon.expected_genesis_hash = Some(mainnet);
+        assert!(daemon.check_cluster().is_ok());
+    }
+
+    #[test]
+    fn weekdays_are_parsed_and_computed_in_utc() {
+        assert_eq!(parse_weekday("Mon"), Some(0));
+        assert_eq!(parse_weekday("friday"), Some(4));
+        assert_eq!(parse_weekday("SUN"), Some(6));
+        assert_eq!(parse_weekday("Mo"), None);
+        assert_eq!(parse_weekday("Fridays"), None);
+        // 1970-01-01 was a Thursday; 2024-06-03 a Monday
+        assert_eq!(weekday(UNIX_EPOCH), 3);
+        let monday = history::parse_date("2024-06-03").unwrap();
+        assert_eq!(weekday(monday), 0);
+        assert_eq!(weekday(monday - Duration::from_secs(1)), 6);
+    }
+
+    #[test]
+    fn balance_history_lists_readings_under_a_sparkline() {
+        let sample = |sol: u64, unix_time: u64| BalanceSample {
+            lamports: sol * LAMPORTS_PER_SOL,
+            unix_time,
+        };
+        let samples = [
+            sample(7, 0),
+            sample(8, 3600),
+            sample(9, 7200),
+            sample(11, 10800),
+        ];
+        assert_eq!(
+            balance_history(&samples),
+            "Recent balances ▁▂▄█\n\
+             1970-01-01T00:00:00Z  7.000000000 SOL\n\
+             1970-01-01T01:00:00Z  8.000000000 SOL\n\
+             1970-01-01T02:00:00Z  9.000000000 SOL\n\
+             1970-01-01T03:00:00Z  11.000000000 SOL"
+        );
+        // A flat balance does not divide by zero
+        assert_eq!(
+            balance_history(&[sample(7, 0)]).lines().next(),
+            Some("Recent balances ▁")
+        );
+    }
+
+    #[test]
+    fn tiers_split_the_balance_highest_first() {
+        let (ops, cold) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tiers = [(10 * LAMPORTS_PER_SOL, ops), (50 * LAMPORTS_PER_SOL, cold)];
+        let threshold = 7 * LAMPORTS_PER_SOL;
+        assert_eq!(
+            tier_transfers(&tiers, threshold, 9 * LAMPORTS_PER_SOL),
+            vec![]
+        );
+        assert_eq!(
+            tier_transfers(&tiers, threshold, 60 * LAMPORTS_PER_SOL),
+            vec![
+                (50 * LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL, cold),
+                (10 * LAMPORTS_PER_SOL, 40 * LAMPORTS_PER_SOL, ops),
+            ]
+        );
+        // A tier below the threshold only gets the balance above the threshold
+        let tiers = [(5 * LAMPORTS_PER_SOL, ops)];
+        assert_eq!(
+            tier_transfers(&tiers, threshold, 8 * LAMPORTS_PER_SOL),
+            vec![(threshold, LAMPORTS_PER_SOL, ops)]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tiered_cycle_makes_one_transfer_per_tier() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.set_dry_run(true);
+        daemon.tiers = vec![
+            (10 * LAMPORTS_PER_SOL, Pubkey::new_unique()),
+            (50 * LAMPORTS_PER_SOL, Pubkey::new_unique()),
+        ];
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            daemon.simulate_cycle(9 * LAMPORTS_PER_SOL, hour).await,
+            CycleOutcome::Idle
+        );
+        assert_eq!(
+            daemon.simulate_cycle(60 * LAMPORTS_PER_SOL, hour).await,
+            CycleOutcome::WouldTransfer(50 * LAMPORTS_PER_SOL)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streamed_balance_crossing_the_trigger_wakes_the_cycle() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let (updates, receiver) = watch::channel(None);
+        daemon.balance_stream = Some(BalanceStream {
+            updates: receiver,
+            last: None,
+            trigger: None,
+            woken_at: None,
+        });
+        async fn crossed(daemon: &mut Daemon) -> Option<u64> {
+            let stream = daemon.balance_stream.as_mut().unwrap();
+            timeout(Duration::from_millis(50), stream.crossed())
+                .await
+                .ok()
+        }
+        // Nothing wakes the loop before a cycle has set the trigger
+        updates.send_replace(Some(9 * LAMPORTS_PER_SOL));
+        assert_eq!(crossed(&mut daemon).await, None);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+
+        updates.send_replace(Some(6 * LAMPORTS_PER_SOL));
+        assert_eq!(crossed(&mut daemon).await, None);
+        updates.send_replace(Some(9 * LAMPORTS_PER_SOL));
+        assert_eq!(crossed(&mut daemon).await, Some(9 * LAMPORTS_PER_SOL));
+        // The cycle sweeps the streamed balance without reading it (the mock reads 50 lamports)
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+
+        // Staying above the trigger does not wake it again; a reconnected stream does
+        updates.send_replace(Some(10 * LAMPORTS_PER_SOL));
+        assert_eq!(crossed(&mut daemon).await, None);
+        updates.send_replace(None);
+        assert_eq!(crossed(&mut daemon).await, None);
+        updates.send_replace(Some(10 * LAMPORTS_PER_SOL));
+        assert_eq!(crossed(&mut daemon).await, Some(10 * LAMPORTS_PER_SOL));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn simulation_reaches_no_endpoint_and_leaves_the_failover_lock_alone() {
+        let path = std::env::temp_dir().join(format!("aft-simulate-lock-{}", std::process::id()));
+        let mut daemon = test_daemon(mock_rpc("fails", HashMap::new()));
+        daemon.set_dry_run(true);
+        daemon.confirm_rpc = Some(mock_rpc("fails", HashMap::new()));
+        daemon.quorum_rpcs = vec![("a".to_string(), mock_rpc("fails", HashMap::new()))];
+        daemon.quorum_balance_reads = 2;
+        daemon.failover = Some(failover::Lock::new(
+            path.clone(),
+            "a".into(),
+            failover::Role::Active,
+            Duration::from_secs(3600),
+        ));
+        assert_eq!(
+            daemon
+                .simulate_cycle(9 * LAMPORTS_PER_SOL, Duration::ZERO)
+                .await,
+            CycleOutcome::WouldTransfer(2 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            daemon.quorum_rpcs[0].1.get_balance(&daemon.sender).unwrap(),
+            9 * LAMPORTS_PER_SOL
+        );
+        assert!(daemon.confirm_rpc().get_balance(&daemon.sender).is_ok());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn balance_quorum_checks_the_read_balance_once_and_never_in_dry_runs() {
+        let read = || {
+            let mut mocks = HashMap::new();
+            mocks.insert(RpcRequest::GetBalance, mock_response(60 * LAMPORTS_PER_SOL));
+            mocks
+        };
+        let mut daemon = test_daemon(mock_rpc("succeeds", read()));
+        daemon.tiers = vec![
+            (10 * LAMPORTS_PER_SOL, Pubkey::new_unique()),
+            (50 * LAMPORTS_PER_SOL, Pubkey::new_unique()),
+        ];
+        // Agrees once; a re-read for the lower tier would see the mock's default 50 lamports
+        daemon.quorum_rpcs = vec![("a".to_string(), mock_rpc("succeeds", read()))];
+        daemon.quorum_balance_reads = 2;
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert_eq!(daemon.metrics.transfers_total.get(), 2);
+
+        // A dry run never queries the quorum endpoints
+        daemon.rpc = mock_rpc("succeeds", read());
+        daemon.quorum_rpcs = vec![("b".to_string(), mock_rpc("fails", HashMap::new()))];
+        daemon.set_dry_run(true);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::WouldTransfer(_)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn large_excess_is_sent_in_chunks_with_a_partial_last_one() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.chunk_lamports = Some(3 * LAMPORTS_PER_SOL / 4);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        // 2 SOL excess: 0.75 + 0.75 + 0.5
+        assert_eq!(daemon.metrics.transfers_total.get(), 3);
+        assert_eq!(daemon.metrics.transfer_sol.get_sample_sum(), 2.0);
+        // The net gauge covers the whole sweep, not its last chunk (the mock receipts carry no fee)
+        assert_eq!(daemon.metrics.transfer_net_sol.get(), 2.0);
+        assert_eq!(daemon.state.transfer_sequence, 3);
+        // One sweep for the dry spell check, however many chunks
+        assert_eq!(daemon.state.transfer_times.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_is_idle_below_threshold() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(7 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_transfers_excess() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert_eq!(daemon.metrics.transfers_total.get(), 1);
+        assert_eq!(daemon.state.transfer_sequence, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn profile_records_rpc_phase_and_cycle_timings() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.set_profile(true);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+        assert_eq!(daemon.metrics.cycle_seconds.get_sample_count(), 1);
+        assert_eq!(
+            daemon
+                .metrics
+                .rpc_phase_seconds
+                .with_label_values(&["getBalance"])
+                .get_sample_count(),
+            1
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn zero_balance_after_nonzero_is_alerted_once() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        // A zero first reading has nothing to compare against
+        daemon.check_zero_balance(0).await;
+        daemon.check_zero_balance(LAMPORTS_PER_SOL).await;
+        assert_eq!(daemon.last_nonzero_balance, Some(LAMPORTS_PER_SOL));
+        daemon.check_zero_balance(0).await;
+        assert_eq!(daemon.last_nonzero_balance, None);
+    }
+
+    #[test]
+    fn obligations_within_horizon_are_retained_until_due() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let now = SystemTime::now();
+        let day = Duration::from_secs(SECONDS_PER_DAY);
+        daemon.obligations = vec![
+            (now - day, LAMPORTS_PER_SOL),
+            (now + 10 * day, 2 * LAMPORTS_PER_SOL),
+            (now + 60 * day, 4 * LAMPORTS_PER_SOL),
+        ];
+        assert_eq!(daemon.obligation_reserve(), 2 * LAMPORTS_PER_SOL);
+        // The past obligation expired; the distant one is kept for later
+        assert_eq!(daemon.obligations.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rent_exempt_minimum_is_kept_above_threshold() {
+        // The mock reports a rent-exempt minimum of 20 lamports
+        let mut mocks = HashMap::new();
+        mocks.insert(
+            RpcRequest::GetBalance,
+            mock_response(7 * LAMPORTS_PER_SOL + 1_000),
+        );
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.reserve_rent_exempt = true;
+        daemon.set_dry_run(true);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::WouldTransfer(980));
+        assert_eq!(daemon.rent_exempt_minimum, Some(20));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn on_chain_threshold_overrides_local_config() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.config_account = Some(Pubkey::new_unique());
+        let on_chain = OnChainConfig {
+            threshold_lamports: Some(10 * LAMPORTS_PER_SOL),
+            poll_interval: Some(Duration::from_secs(60)),
+        };
+        daemon.on_chain_config = Some((Some(on_chain), Instant::now()));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+        assert_eq!(daemon.next_poll_interval, Duration::from_secs(60));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unavailable_config_account_falls_back_to_local_config() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        // The mock serves no account data
+        daemon.config_account = Some(Pubkey::new_unique());
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert!(matches!(daemon.on_chain_config, Some((None, _))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfer_deferred_when_finalized_balance_lacks_excess() {
+        let mut mocks = HashMap::new();
+        // Only the (lower-commitment) cycle read sees the excess; the finalized read gets the
+        // mock's default 50 lamports
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.balance_commitment = CommitmentConfig::confirmed();
+        daemon.finalized_recheck = true;
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfer_to_vote_account_is_refused_unless_allowed() {
+        let vote_account = json!({
+            "lamports": LAMPORTS_PER_SOL,
+            "data": ["", "base64"],
+            "owner": VOTE_PROGRAM_ID.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": 0,
+        });
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        mocks.insert(
+            RpcRequest::GetAccountInfo,
+            mock_response(vote_account.clone()),
+        );
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+
+        // The override also skips the lookup
+        daemon.allow_vote_account_receiver = true;
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        mocks.insert(RpcRequest::GetAccountInfo, mock_response(vote_account));
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unapproved_large_transfer_is_cancelled() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.approval = Some(
+            Approval::new(
+                LAMPORTS_PER_SOL,
+                approval::Source::File(std::env::temp_dir().join("aft-no-such-approval-file")),
+                Duration::ZERO,
+                Duration::from_secs(1),
+            )
+            .unwrap(),
+        );
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refused_receiver_routes_to_fallback() {
+        let fallback = Pubkey::new_unique();
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.allow_vote_account_receiver = true;
+        daemon.receiver_allowlist = vec![fallback];
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+
+        daemon.fallback_receiver = Some(fallback);
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn simulated_balances_produce_dry_run_decisions() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.set_dry_run(true);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            daemon.simulate_cycle(7 * LAMPORTS_PER_SOL, hour).await,
+            CycleOutcome::Idle
+        );
+        assert_eq!(
+            daemon.simulate_cycle(9 * LAMPORTS_PER_SOL, hour).await,
+            CycleOutcome::WouldTransfer(2 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sweep_waits_for_excess_to_persist() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.set_dry_run(true);
+        daemon.sweep_delay = Some(Duration::from_secs(3600));
+        let excess = 9 * LAMPORTS_PER_SOL;
+        let minute = Duration::from_secs(60);
+
+        // Held, then cancelled when the excess disappears
+        assert_eq!(
+            daemon.simulate_cycle(excess, minute).await,
+            CycleOutcome::Deferred
+        );
+        daemon.simulate_cycle(LAMPORTS_PER_SOL, minute).await;
+        assert_eq!(daemon.excess_since, None);
+
+        // Held again from scratch, swept once it has persisted past the delay
+        assert_eq!(
+            daemon.simulate_cycle(excess, minute).await,
+            CycleOutcome::Deferred
+        );
+        assert_eq!(
+            daemon
+                .simulate_cycle(excess, Duration::from_secs(3600))
+                .await,
+            CycleOutcome::WouldTransfer(2 * LAMPORTS_PER_SOL)
+        );
+    }
+
+    #[test]
+    fn amount_band_is_chosen_by_excess() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let (hot, cold) = (Pubkey::new_unique(), Pubkey::new_unique());
+        daemon.amount_bands = vec![(LAMPORTS_PER_SOL, hot), (50 * LAMPORTS_PER_SOL, cold)];
+        assert_eq!(daemon.band_receiver(LAMPORTS_PER_SOL / 2), daemon.receiver);
+        assert_eq!(daemon.band_receiver(LAMPORTS_PER_SOL), hot);
+        assert_eq!(daemon.band_receiver(50 * LAMPORTS_PER_SOL - 1), hot);
+        assert_eq!(daemon.band_receiver(50 * LAMPORTS_PER_SOL), cold);
+    }
+
+    #[test]
+    fn receiver_window_overrides_the_default_receiver_while_active() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        let (q1, q2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let day = |d: u64| UNIX_EPOCH + Duration::from_secs(d * 86_400);
+        daemon.receiver_windows = vec![(day(10), day(20), q1), (day(20), day(30), q2)];
+        assert_eq!(daemon.default_receiver(day(9)), daemon.receiver);
+        assert_eq!(daemon.default_receiver(day(10)), q1);
+        // Windows end exclusively, so back-to-back windows hand over cleanly
+        assert_eq!(daemon.default_receiver(day(20)), q2);
+        assert_eq!(daemon.default_receiver(day(30)), daemon.receiver);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn first_transfer_is_capped_once() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.first_transfer_max_lamports = Some(LAMPORTS_PER_SOL);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert_eq!(daemon.metrics.transfer_sol.get_sample_sum(), 1.0);
+        assert_eq!(daemon.first_transfer_max_lamports, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_defers_while_pause_file_exists() {
+        let path = std::env::temp_dir().join(format!("aft-pause-test-{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.pause_file = Some(path.clone());
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn standby_transfers_only_once_the_active_heartbeat_is_stale() {
+        let path = std::env::temp_dir().join(format!("aft-failover-test-{}", std::process::id()));
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        std::fs::write(&path, json!({"holder": "a", "unix_time": now}).to_string()).unwrap();
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks.clone()));
+        daemon.failover = Some(failover::Lock::new(
+            path.clone(),
+            "b".into(),
+            failover::Role::Standby,
+            Duration::from_secs(3600),
+        ));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+
+        // The active instance stopped renewing its heartbeat
+        std::fs::write(&path, json!({"holder": "a", "unix_time": 0}).to_string()).unwrap();
+        daemon.rpc = mock_rpc("succeeds", mocks);
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+        assert_eq!(daemon.failover_active, Some(true));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn receipt_reports_slot_fee_and_balances() {
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        // The accounts of the mock's canned transaction
+        daemon.sender = "C6eBmAXKg6JhJWkajGa5YRGUfG4YKXwbxF5Ufv7PtExZ"
+            .parse()
+            .unwrap();
+        // Transferred to, e.g., the fallback receiver rather than the configured one
+        let receiver: Pubkey = "2Gd5eoR5J4BV89uXbtunpbNhjmw3wa1NbRHxTHzDzZLX"
+            .parse()
+            .unwrap();
+        assert_ne!(daemon.receiver, receiver);
+        let cycle = Cycle::new(Span::none());
+        let receipt = daemon
+            .fetch_receipt(&cycle, &Signature::default(), &receiver)
+            .await
+            .unwrap();
+        assert_eq!(
+            receipt,
+            Receipt {
+                slot: 2,
+                fee_lamports: 0,
+                sender_balances: Some((499999999999999950, 499999999999999950)),
+                receiver_balances: Some((50, 50)),
+            }
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_waits_for_relative_trigger() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(8 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        // 20% above the 7 SOL threshold
+        daemon.trigger_lamports = 8_400_000_000;
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Idle);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_skips_receiver_above_expected_ceiling() {
+        // A mocked response is used once: the sender reads 9 SOL, then the receiver reads the
+        // mock's default of 50 lamports, above the 10 lamport ceiling
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", mocks));
+        daemon.receiver_max_expected_lamports = Some(10);
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn migrate_receiver_requires_test_transfer_before_confirm() {
+        let state_path =
+            std::env::temp_dir().join(format!("aft-migrate-test-{}.json", std::process::id()));
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.state_path = Some(state_path.clone());
+        let new = Pubkey::new_unique();
+
+        assert!(daemon.migrate_receiver(new, true, 1_000).is_err());
+        daemon.migrate_receiver(new, false, 1_000).unwrap();
+        assert_eq!(daemon.state.pending_receiver, Some(new.to_string()));
+        daemon.migrate_receiver(new, true, 1_000).unwrap();
+
+        let state = State::load(&state_path).unwrap();
+        assert_eq!(state.receiver_override, Some(new.to_string()));
+        assert_eq!(state.pending_receiver, None);
+        std::fs::remove_file(state_path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_cycle_defers_failed_transfer() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("instruction_error", mocks));
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+    }
+
+    /// A mock RPC node that takes `delay` to answer `slow` requests.
+    struct SlowSender {
+        inner: MockSender,
+        slow: RpcRequest,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl RpcSender for SlowSender {
+        async fn send(
+            &self,
+            request: RpcRequest,
+            params: Value,
+        ) -> solana_client::client_error::Result<Value> {
+            if request == self.slow {
+                sleep(self.delay).await;
+            }
+            self.inner.send(request, params).await
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.inner.get_transport_stats()
+        }
+
+        fn url(&self) -> String {
+            self.inner.url()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn slow_confirmation_cannot_outlast_the_cycle_budget() {
+        let mut mocks = HashMap::new();
+        mocks.insert(RpcRequest::GetBalance, mock_response(9 * LAMPORTS_PER_SOL));
+        let mut daemon = test_daemon(mock_rpc("succeeds", HashMap::new()));
+        daemon.cycle_budget = Some(Duration::from_secs(2));
+        daemon.rpc = RpcClient::new_sender(
+            DeadlineSender::new(
+                SlowSender {
+                    inner: MockSender::new_with_mocks("succeeds", mocks),
+                    slow: RpcRequest::GetSignatureStatuses,
+                    delay: Duration::from_secs(60),
+                },
+                daemon.rpc_deadline.clone(),
+            ),
+            RpcClientConfig::with_commitment(CommitmentConfig::finalized()),
+        );
+        let started = Instant::now();
+        assert_eq!(daemon.check_cycle().await, CycleOutcome::Deferred);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // The transfer was sent, so its outcome is unknown rather than failed
+        assert!(daemon.in_flight.is_some());
+        assert_eq!(daemon.metrics.transfers_total.get(), 0);
+        assert_eq!(daemon.rpc_deadline.remaining(), None);
+    }
+
+    /// A `solana-test-validator` child process, killed (and its ledger removed) on drop.
+    struct TestValidator {
+        child: std::process::Child,
+        ledger: PathBuf,
+    }
+
+    impl Drop for TestValidator {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = std::fs::remove_dir_all(&self.ledger);
+        }
+    }
+
+    /// Poll `check` once a second until it holds, panicking after `timeout`.
+    fn wait_for(what: &str, timeout: Duration, mut check: impl FnMut() -> bool) {
+        let deadline = Instant::now() + timeout;
+        while !check() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// End-to-end happy path against a real local cluster. Needs `solana-test-validator` on
+    /// PATH: `cargo test -- --ignored test_validator`.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs solana-test-validator on PATH"]
+    async fn test_validator_sweeps_excess_to_receiver() {
+        let rpc_port = 18899;
+        let ledger = std::env::temp_dir().join(format!("aft-test-ledger-{}", std::process::id()));
+        let child = std::process::Command::new("solana-test-validator")
+            .args(["--reset", "--quiet", "--rpc-port", &rpc_port.to_string()])
+            .arg("--ledger")
+            .arg(&ledger)
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .expect("starting solana-test-validator");
+        let _validator = TestValidator { child, ledger };
+        let url = format!("http://127.0.0.1:{}", rpc_port);
+
+        let rpc = RpcClient::new_with_commitment(url.clone(), CommitmentConfig::finalized());
+        wait_for("validator health", Duration::from_secs(60), || {
+            rpc.get_health().is_ok()
+        });
+
+        let mut daemon = test_daemon(RpcClient::new_with_commitment(
+            url,
+            CommitmentConfig::finalized(),
+        ));
+        daemon.reserve_rent_exempt = true;
+        let (sender, receiver) = (daemon.sender, daemon.receiver);
+        rpc.request_airdrop(&sender, 10 * LAMPORTS_PER_SOL)
+            .expect("requesting airdrop");
+        wait_for("finalized airdrop", Duration::from_secs(60), || {
+            rpc.get_balance(&sender).unwrap_or(0) == 10 * LAMPORTS_PER_SOL
+        });
+
+        assert!(matches!(
+            daemon.check_cycle().await,
+            CycleOutcome::Transferred(_)
+        ));
+
+        let rent_exempt_minimum = rpc.get_minimum_balance_for_rent_exemption(0).unwrap();
+        let keep = daemon.threshold_lamports + rent_exempt_minimum;
+        assert_eq!(
+            rpc.get_balance(&receiver).unwrap(),
+            10 * LAMPORTS_PER_SOL - keep
+        );
+        // The sender lands at the threshold, less the fee it paid for the transfer
+        let sender_balance = rpc.get_balance(&sender).unwrap();
+        assert!(sender_balance < keep);
+        assert!(
+            keep - sender_balance <= 10_000,
+            "fee of {} lamports",
+            keep - sender_balance
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Warm-standby pairs (`role`, `failover_lock_file`): of two daemons watching the same sender,
+//! only the holder of a heartbeat lock file on shared storage transfers.
+//!
+//! Every cycle the holder rewrites the file with its `deployment_name` and the current time.
+//! Any instance may take over a lock whose heartbeat is older than `failover_stale_seconds`;
+//! a `standby` also waits that long for an `active` instance to appear before taking a missing
+//! lock. Takeover is a write to a private temporary file renamed over the lock, then a re-read:
+//! when two instances race, the last rename wins and the other sees a foreign holder and stays
+//! standby. The holder re-checks the lock right before each send, and an instance that finds
+//! another holder's fresh heartbeat demotes itself.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Which instance of a pair starts out transferring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Active,
+    Standby,
+}
+
+/// Contents of the lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Heartbeat {
+    holder: String,
+    unix_time: u64,
+}
+
+/// The shared lock of a warm-standby pair.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    path: PathBuf,
+    /// This instance's `deployment_name`; must differ between the instances of a pair.
+    holder: String,
+    role: Role,
+    stale: Duration,
+    started: SystemTime,
+}
+
+impl Lock {
+    pub fn new(path: PathBuf, holder: String, role: Role, stale: Duration) -> Self {
+        Self {
+            path,
+            holder,
+            role,
+            stale,
+            started: SystemTime::now(),
+        }
+    }
+
+    /// Renew the lock, or take it over if it is free, and report whether this instance holds
+    /// it (and may transfer).
+    pub fn refresh(&self) -> Result<bool> {
+        self.refresh_at(SystemTime::now())
+    }
+
+    fn refresh_at(&self, now: SystemTime) -> Result<bool> {
+        let may_take = match self.read()? {
+            Some(current) if current.holder == self.holder => true,
+            Some(current) => {
+                unix_time(now).saturating_sub(current.unix_time) >= self.stale.as_secs()
+            }
+            None => {
+                self.role == Role::Active
+                    || now.duration_since(self.started).unwrap_or_default() >= self.stale
+            }
+        };
+        if !may_take {
+            return Ok(false);
+        }
+        let heartbeat = Heartbeat {
+            holder: self.holder.clone(),
+            unix_time: unix_time(now),
+        };
+        let tmp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, serde_json::to_string(&heartbeat)?)
+            .with_context(|| format!("writing failover lock {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replacing failover lock {}", self.path.display()))?;
+        // Another instance may have taken it at the same moment; the last rename wins
+        Ok(self
+            .read()?
+            .is_some_and(|current| current.holder == self.holder))
+    }
+
+    /// Holder of the lock, if any.
+    pub fn holder(&self) -> Result<Option<String>> {
+        Ok(self.read()?.map(|current| current.holder))
+    }
+
+    fn read(&self) -> Result<Option<Heartbeat>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map(Some)
+                .with_context(|| format!("parsing failover lock {}", self.path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => {
+                Err(e).with_context(|| format!("reading failover lock {}", self.path.display()))
+            }
+        }
+    }
+}
+
+fn unix_time(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standby_takes_over_a_stale_lock_and_active_steps_down() {
+        let path = std::env::temp_dir().join(format!("aft-failover-{}.lock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let stale = Duration::from_secs(60);
+        let active = Lock::new(path.clone(), "a".into(), Role::Active, stale);
+        let standby = Lock::new(path.clone(), "b".into(), Role::Standby, stale);
+        let t0 = SystemTime::now();
+
+        // A missing lock: the active instance takes it, the standby waits for it
+        assert!(!standby.refresh_at(t0).unwrap());
+        assert!(active.refresh_at(t0).unwrap());
+        assert!(!standby.refresh_at(t0 + stale / 2).unwrap());
+
+        // The active instance stops renewing: the standby promotes itself
+        assert!(standby.refresh_at(t0 + stale).unwrap());
+        assert_eq!(active.holder().unwrap(), Some("b".to_string()));
+        assert!(!active.refresh_at(t0 + stale).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+//! Reporting of the error that ends the process, as plain text or (`--json-errors`) as one JSON
+//! object on stderr: `{"error": "...", "kind": "...", "exit_code": N}`.
+//!
+//! Exit codes follow sysexits(3) so scripts can tell a broken config from an unreachable RPC
+//! node without parsing the message.
+
+use serde_json::json;
+use solana_client::client_error::ClientError;
+use std::fmt;
+
+/// Category of a fatal error. Attach one as context (`.context(Kind::Config)`) where the
+/// category is known; otherwise it is inferred from the error chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Invalid command line.
+    Usage,
+    /// Unreadable or invalid config, keypair or state file.
+    Config,
+    /// RPC node unreachable, unhealthy or returning errors.
+    Rpc,
+    /// Anything else, including a panicked poll loop.
+    Runtime,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Usage => "usage",
+            Kind::Config => "config",
+            Kind::Rpc => "rpc",
+            Kind::Runtime => "runtime",
+        }
+    }
+
+    /// Process exit code for this kind.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Kind::Usage => 64,   // EX_USAGE
+            Kind::Config => 78,  // EX_CONFIG
+            Kind::Rpc => 69,     // EX_UNAVAILABLE
+            Kind::Runtime => 70, // EX_SOFTWARE
+        }
+    }
+
+    /// The kind attached to `err` as context, else `Rpc` for an RPC client error anywhere in
+    /// its chain, else `Runtime`.
+    pub fn of(err: &anyhow::Error) -> Kind {
+        if let Some(kind) = err.downcast_ref::<Kind>() {
+            return *kind;
+        }
+        if err.chain().any(|e| e.is::<ClientError>()) {
+            return Kind::Rpc;
+        }
+        Kind::Runtime
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Usage => "invalid command line",
+            Kind::Config => "invalid configuration",
+            Kind::Rpc => "RPC unavailable",
+            Kind::Runtime => "runtime failure",
+        })
+    }
+}
+
+/// The `--json-errors` object for `err`.
+fn to_json(err: &anyhow::Error, kind: Kind) -> serde_json::Value {
+    json!({
+        "error": format!("{:#}", err),
+        "kind": kind.as_str(),
+        "exit_code": kind.exit_code(),
+    })
+}
+
+/// Print `err` to stderr and exit the process with the code of its kind.
+pub fn exit(err: &anyhow::Error, json_errors: bool) -> ! {
+    let kind = Kind::of(err);
+    if json_errors {
+        eprintln!("{}", to_json(err, kind));
+    } else {
+        eprintln!("Error: {:?}", err);
+    }
+    std::process::exit(kind.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+    use solana_sdk::transaction::TransactionError;
+
+    #[test]
+    fn kind_comes_from_context_or_error_chain() {
+        let err = Err::<(), _>(anyhow!("missing field `receiver_pubkey`"))
+            .context(Kind::Config)
+            .unwrap_err();
+        assert_eq!(Kind::of(&err), Kind::Config);
+        assert_eq!(
+            to_json(&err, Kind::Config),
+            json!({
+                "error": "invalid configuration: missing field `receiver_pubkey`",
+                "kind": "config",
+                "exit_code": 78,
+            })
+        );
+
+        let err = anyhow::Error::from(ClientError::from(TransactionError::BlockhashNotFound))
+            .context("fetching balance");
+        assert_eq!(Kind::of(&err), Kind::Rpc);
+        assert_eq!(Kind::of(&anyhow!("poll loop panicked")), Kind::Runtime);
+    }
+}
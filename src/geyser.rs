@@ -0,0 +1,182 @@
+//! Sender balance pushed by a Yellowstone (Geyser) gRPC endpoint (`geyser_endpoint`, `geyser`
+//! cargo feature), so the poll loop reacts to an excess as soon as it appears instead of at the
+//! next poll.
+//!
+//! The subscription only wakes the loop early: while the stream is down the channel holds `None`
+//! and the daemon keeps polling every `poll_interval`, as it does without a stream.
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use solana_commitment_config::CommitmentLevel;
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::HashMap, time::Duration};
+use tokio::{sync::watch, time::sleep};
+use tracing::{info, warn};
+use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcBuilder, GeyserGrpcClient};
+use yellowstone_grpc_proto::prelude::{
+    CommitmentLevel as GeyserCommitment, SubscribeRequest, SubscribeRequestFilterAccounts,
+    SubscribeUpdate, subscribe_update::UpdateOneof,
+};
+
+/// First wait before reconnecting a dropped stream; doubled per failed attempt up to the cap.
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// HTTP/2 keep-alive ping interval, so a silently dead connection errors out and is redialed.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Builder for `endpoint`, with `x_token` sent as the `x-token` header. Run at startup so a
+/// malformed endpoint is a config error rather than a stream that never connects.
+pub fn client_builder(endpoint: &str, x_token: Option<&str>) -> Result<GeyserGrpcBuilder> {
+    let mut builder = GeyserGrpcClient::build_from_shared(endpoint.to_string())
+        .context("parsing geyser_endpoint")?
+        .x_token(x_token.map(str::to_string))
+        .context("parsing geyser_x_token")?
+        .connect_timeout(Duration::from_secs(10))
+        .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+        .keep_alive_while_idle(true);
+    if endpoint.starts_with("https://") {
+        builder = builder
+            .tls_config(ClientTlsConfig::new().with_native_roots())
+            .context("configuring TLS for geyser_endpoint")?;
+    }
+    Ok(builder)
+}
+
+/// Subscribe to `account` and publish every balance it reports to `balances`, redialing with
+/// backoff whenever the stream drops. Only returns on a config error.
+pub async fn stream_balance(
+    endpoint: String,
+    x_token: Option<String>,
+    account: Pubkey,
+    commitment: CommitmentLevel,
+    balances: watch::Sender<Option<u64>>,
+) -> Result<()> {
+    let mut backoff = RECONNECT_BACKOFF_MIN;
+    loop {
+        let builder = client_builder(&endpoint, x_token.as_deref())?;
+        let received = subscribe(builder, account, commitment, &balances).await;
+        // Whatever the stream last said may be stale by the time it comes back
+        balances.send_replace(None);
+        let error = match received {
+            Ok(delivered) => {
+                // A stream that worked for a while starts over from the shortest wait
+                if delivered {
+                    backoff = RECONNECT_BACKOFF_MIN;
+                }
+                "stream closed by the server".to_string()
+            }
+            Err(e) => format!("{:#}", e),
+        };
+        warn!(
+            "Geyser balance stream dropped; polling every poll interval until it reconnects: retry_s = {}, error = {}",
+            backoff.as_secs(),
+            error
+        );
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+    }
+}
+
+/// One subscription, until the stream ends; whether it delivered any balance.
+async fn subscribe(
+    builder: GeyserGrpcBuilder,
+    account: Pubkey,
+    commitment: CommitmentLevel,
+    balances: &watch::Sender<Option<u64>>,
+) -> Result<bool> {
+    let mut client = builder.connect().await.context("connecting")?;
+    let mut stream = client
+        .subscribe_once(subscribe_request(account, commitment))
+        .await
+        .context("subscribing")?;
+    info!(
+        "Geyser balance stream connected: account = {}",
+        crate::redact::addr(account)
+    );
+    let mut received = false;
+    while let Some(update) = stream.next().await {
+        let update = update.context("reading the stream")?;
+        if let Some(lamports) = balance_of(&update, &account) {
+            balances.send_replace(Some(lamports));
+            received = true;
+        }
+    }
+    Ok(received)
+}
+
+fn subscribe_request(account: Pubkey, commitment: CommitmentLevel) -> SubscribeRequest {
+    let commitment = match commitment {
+        CommitmentLevel::Processed => GeyserCommitment::Processed,
+        CommitmentLevel::Confirmed => GeyserCommitment::Confirmed,
+        CommitmentLevel::Finalized => GeyserCommitment::Finalized,
+    };
+    SubscribeRequest {
+        accounts: HashMap::from([(
+            "sender".to_string(),
+            SubscribeRequestFilterAccounts {
+                account: vec![account.to_string()],
+                ..Default::default()
+            },
+        )]),
+        commitment: Some(commitment as i32),
+        ..Default::default()
+    }
+}
+
+/// The lamports of `account` carried by an account update; `None` for pings and other accounts.
+fn balance_of(update: &SubscribeUpdate, account: &Pubkey) -> Option<u64> {
+    let Some(UpdateOneof::Account(update)) = &update.update_oneof else {
+        return None;
+    };
+    let info = update.account.as_ref()?;
+    (info.pubkey == account.as_ref()).then_some(info.lamports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yellowstone_grpc_proto::prelude::{
+        SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdatePing,
+    };
+
+    fn account_update(pubkey: &Pubkey, lamports: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    lamports,
+                    ..Default::default()
+                }),
+                slot: 1,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_updates_of_the_sender_carry_a_balance() {
+        let sender = Pubkey::new_unique();
+        assert_eq!(balance_of(&account_update(&sender, 42), &sender), Some(42));
+        assert_eq!(
+            balance_of(&account_update(&Pubkey::new_unique(), 42), &sender),
+            None
+        );
+        let ping = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            ..Default::default()
+        };
+        assert_eq!(balance_of(&ping, &sender), None);
+
+        let request = subscribe_request(sender, CommitmentLevel::Confirmed);
+        assert_eq!(request.accounts["sender"].account, vec![sender.to_string()]);
+        assert_eq!(request.commitment, Some(GeyserCommitment::Confirmed as i32));
+    }
+
+    #[test]
+    fn malformed_endpoint_is_a_config_error() {
+        assert!(client_builder("https://geyser.example.com:443", Some("t0ken")).is_ok());
+        assert!(client_builder("not a url", None).is_err());
+    }
+}
@@ -0,0 +1,558 @@
+//! Append-only JSON-lines ledger of confirmed transfers (and of transfers that failed to land),
+//! and the offline `report` over it.
+//!
+//! With rotation configured, a full history file is moved to a gzip-compressed archive next to
+//! it (`<file>.<timestamp>.gz`) and a fresh file is started; reads cover archives and live file.
+//!
+//! With `audit_sign`, every entry carries a sender-key signature over its canonical JSON form,
+//! checked offline by `verify-audit`. Entries written by `--broadcast`, on a host without the
+//! sender keypair, are marked `signed_offline` instead and skipped by the check.
+
+use anyhow::{Context, Result, anyhow};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::amount;
+
+/// One confirmed transfer, or one that failed to land (`failure`), as written to the history
+/// file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// RFC 3339 UTC timestamp of confirmation.
+    pub timestamp: String,
+    pub signature: String,
+    pub sender: String,
+    pub receiver: String,
+    pub lamports: u64,
+    /// Transaction fee: the fee actually paid when the receipt was fetched, otherwise the
+    /// estimate made before sending (if any).
+    pub fee_lamports: Option<u64>,
+    /// Slot the transaction landed in, if its receipt was fetched.
+    pub slot: Option<u64>,
+    /// Sweep sequence number (also in the transaction memo); consecutive across sweeps so a
+    /// missing entry shows as a gap.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    /// Why the transfer failed to land; `None` for a confirmed transfer. A failed entry moved no
+    /// SOL and is only counted as a failure by the report.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+    /// Base58 sender-key signature over the entry's canonical form (`audit_sign`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_signature: Option<String>,
+    /// Written with `audit_sign` on a host without the sender keypair (`--broadcast`), so it
+    /// carries no audit signature; `verify-audit` lists it as skipped.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub signed_offline: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        signature: String,
+        sender: String,
+        receiver: String,
+        lamports: u64,
+        fee_lamports: Option<u64>,
+        slot: Option<u64>,
+    ) -> Self {
+        Self {
+            timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            signature,
+            sender,
+            receiver,
+            lamports,
+            fee_lamports,
+            slot,
+            sequence: None,
+            failure: None,
+            audit_signature: None,
+            signed_offline: false,
+        }
+    }
+
+    /// The signed form of the entry: its JSON line without the audit signature.
+    fn canonical(&self) -> Vec<u8> {
+        let unsigned = Self {
+            audit_signature: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("history entries always serialize")
+    }
+
+    /// Sign the entry's canonical form with `keypair`, or mark it `signed_offline` when the
+    /// sender keypair is not on this host.
+    pub fn sign(&mut self, keypair: Option<&Keypair>) {
+        match keypair {
+            Some(keypair) => {
+                self.audit_signature = Some(keypair.sign_message(&self.canonical()).to_string())
+            }
+            None => self.signed_offline = true,
+        }
+    }
+
+    /// Whether the entry carries a valid audit signature by `signer`.
+    fn verify(&self, signer: &Pubkey) -> bool {
+        self.audit_signature
+            .as_deref()
+            .and_then(|sig| sig.parse::<Signature>().ok())
+            .is_some_and(|sig| sig.verify(signer.as_ref(), &self.canonical()))
+    }
+}
+
+/// Size-based rotation of the history file.
+#[derive(Debug, Clone, Copy)]
+pub struct Rotation {
+    /// Rotate once the live file exceeds this many bytes.
+    pub max_bytes: u64,
+    /// Number of compressed archives kept; older ones are deleted.
+    pub keep_files: usize,
+}
+
+/// Append one entry to the history file, creating it if needed. With `rotation`, a file that
+/// already exceeds the size limit is archived first.
+pub fn append(path: &Path, entry: &HistoryEntry, rotation: Option<Rotation>) -> Result<()> {
+    if let Some(rotation) = rotation {
+        let size = match fs::metadata(path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading history file {}", path.display()));
+            }
+        };
+        if size > rotation.max_bytes {
+            rotate(path, rotation.keep_files)?;
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening history file {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .with_context(|| format!("writing history file {}", path.display()))?;
+    Ok(())
+}
+
+/// Compress the live file into a new timestamped archive, remove it, and prune old archives.
+fn rotate(path: &Path, keep_files: usize) -> Result<()> {
+    // Nanosecond timestamps keep archive names unique and in chronological (lexical) order
+    let stamp: String = humantime::format_rfc3339_nanos(SystemTime::now())
+        .to_string()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    let mut archive_name = path.file_name().unwrap_or_default().to_os_string();
+    archive_name.push(format!(".{}.gz", stamp));
+    let archive = path.with_file_name(archive_name);
+
+    let mut input =
+        File::open(path).with_context(|| format!("opening history file {}", path.display()))?;
+    let output = File::create(&archive)
+        .with_context(|| format!("creating history archive {}", archive.display()))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)
+        .and_then(|_| encoder.finish())
+        .with_context(|| format!("writing history archive {}", archive.display()))?;
+    fs::remove_file(path).with_context(|| format!("removing history file {}", path.display()))?;
+
+    let archives = archives(path)?;
+    for old in &archives[..archives.len().saturating_sub(keep_files)] {
+        fs::remove_file(old)
+            .with_context(|| format!("removing history archive {}", old.display()))?;
+    }
+    Ok(())
+}
+
+/// Compressed archives of the history file, oldest first.
+fn archives(path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!(
+        "{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let mut archives: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("listing history directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".gz"))
+        })
+        .collect();
+    archives.sort();
+    Ok(archives)
+}
+
+fn parse_lines(text: &str, source: &Path) -> Result<Vec<HistoryEntry>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("parsing {} line {}", source.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Read every entry of the history file and its archives, oldest first. Blank lines are
+/// ignored.
+pub fn read_all(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let archives = archives(path)?;
+    let mut entries = Vec::new();
+    for archive in &archives {
+        let mut text = String::new();
+        GzDecoder::new(
+            File::open(archive)
+                .with_context(|| format!("opening history archive {}", archive.display()))?,
+        )
+        .read_to_string(&mut text)
+        .with_context(|| format!("reading history archive {}", archive.display()))?;
+        entries.extend(parse_lines(&text, archive)?);
+    }
+    match fs::read_to_string(path) {
+        Ok(text) => entries.extend(parse_lines(&text, path)?),
+        // Right after a rotation only archives exist
+        Err(e) if e.kind() == ErrorKind::NotFound && !archives.is_empty() => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("reading history file {}", path.display()));
+        }
+    }
+    Ok(entries)
+}
+
+/// Highest sweep sequence number recorded in the history file and its archives; `None` if
+/// there is no history yet or no entry carries one.
+pub fn last_sequence(path: &Path) -> Result<Option<u64>> {
+    if !path.exists() && archives(path)?.is_empty() {
+        return Ok(None);
+    }
+    Ok(read_all(path)?.iter().filter_map(|e| e.sequence).max())
+}
+
+/// Parse a `--since` value: either a date (`2024-01-01`) or a full RFC 3339 timestamp.
+pub fn parse_since(value: &str) -> Result<SystemTime> {
+    parse_date(value).map_err(|e| anyhow!("invalid --since value {:?}: {}", value, e))
+}
+
+/// Parse a date (`2024-01-01`, midnight UTC) or a full RFC 3339 timestamp.
+pub fn parse_date(value: &str) -> Result<SystemTime, humantime::TimestampError> {
+    let normalized = if value.len() == 10 {
+        format!("{}T00:00:00Z", value)
+    } else {
+        value.to_string()
+    };
+    humantime::parse_rfc3339_weak(&normalized)
+}
+
+/// Span of recent history fee projections are estimated from, and the "month" they scale to.
+pub const FEE_PROJECTION_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Sweep economics estimated from recent confirmed transfers (`fee_report_interval_seconds`),
+/// scaled to `FEE_PROJECTION_WINDOW`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeProjection {
+    pub transfers_per_month: f64,
+    pub fee_lamports_per_month: u64,
+    /// Lamports swept per month: the rate excess builds up on the sender.
+    pub swept_lamports_per_month: u64,
+    /// Mean fee of one transfer, over the transfers whose fee is known.
+    pub average_fee_lamports: u64,
+}
+
+/// Project transfers, fees and the excess build-up rate from the confirmed transfers of the
+/// `FEE_PROJECTION_WINDOW` before `now`. Rates are measured between the first and the last of
+/// them, so the first transfer's amount (built up before the window) is left out. `None` with
+/// fewer than two transfers.
+pub fn fee_projection(entries: &[HistoryEntry], now: SystemTime) -> Option<FeeProjection> {
+    let since = now.checked_sub(FEE_PROJECTION_WINDOW)?;
+    let recent: Vec<(SystemTime, &HistoryEntry)> = entries
+        .iter()
+        .filter(|e| e.failure.is_none())
+        .filter_map(|e| Some((humantime::parse_rfc3339(&e.timestamp).ok()?, e)))
+        .filter(|(ts, _)| *ts >= since && *ts <= now)
+        .collect();
+    let [(first, _), .., (last, _)] = recent.as_slice() else {
+        return None;
+    };
+    let span = last.duration_since(*first).ok()?.as_secs_f64();
+    if span == 0.0 {
+        return None;
+    }
+    let scale = FEE_PROJECTION_WINDOW.as_secs_f64() / span;
+    let later = &recent[1..];
+    let fees: Vec<u64> = recent.iter().filter_map(|(_, e)| e.fee_lamports).collect();
+    let average_fee_lamports = (fees.iter().sum::<u64>())
+        .checked_div(fees.len() as u64)
+        .unwrap_or(0);
+    Some(FeeProjection {
+        transfers_per_month: later.len() as f64 * scale,
+        fee_lamports_per_month: (later.len() as f64 * average_fee_lamports as f64 * scale) as u64,
+        swept_lamports_per_month: (later.iter().map(|(_, e)| e.lamports).sum::<u64>() as f64
+            * scale) as u64,
+        average_fee_lamports,
+    })
+}
+
+#[derive(Default)]
+struct Totals {
+    count: u64,
+    lamports: u64,
+    fee_lamports: u64,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &HistoryEntry) {
+        self.count += 1;
+        self.lamports += entry.lamports;
+        self.fee_lamports += entry.fee_lamports.unwrap_or(0);
+    }
+}
+
+/// Aggregate the history file over entries at or after `since` and print the report to stdout.
+pub fn print_report(path: &Path, since: Option<SystemTime>) -> Result<()> {
+    let mut total = Totals::default();
+    let mut per_receiver: BTreeMap<String, Totals> = BTreeMap::new();
+    let mut failed = 0;
+
+    for entry in read_all(path)? {
+        let ts = humantime::parse_rfc3339(&entry.timestamp)
+            .with_context(|| format!("parsing timestamp of {}", entry.signature))?;
+        if since.is_some_and(|since| ts < since) {
+            continue;
+        }
+        if entry.failure.is_some() {
+            failed += 1;
+            continue;
+        }
+        total.add(&entry);
+        per_receiver
+            .entry(entry.receiver.clone())
+            .or_default()
+            .add(&entry);
+    }
+
+    match since {
+        Some(since) => println!(
+            "Transfer report since {}",
+            humantime::format_rfc3339_seconds(since)
+        ),
+        None => println!("Transfer report (all history)"),
+    }
+    println!("  transfers:        {}", total.count);
+    println!("  total_sol:        {}", amount::sol(total.lamports));
+    println!("  total_lamports:   {}", total.lamports);
+    let average = total.lamports.checked_div(total.count).unwrap_or(0);
+    println!("  average_sol:      {}", amount::sol(average));
+    println!("  total_fees_sol:   {}", amount::sol(total.fee_lamports));
+    if failed > 0 {
+        println!("  failed_to_land:   {}", failed);
+    }
+    if !per_receiver.is_empty() {
+        println!("  per receiver:");
+        for (receiver, t) in &per_receiver {
+            println!(
+                "    {}: transfers = {}, total_sol = {}, fees_sol = {}",
+                receiver,
+                t.count,
+                amount::sol(t.lamports),
+                amount::sol(t.fee_lamports)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check the audit signature of every history entry against `signer` and print the result.
+/// Fails if any entry is unsigned or its signature does not verify; entries marked
+/// `signed_offline` are listed as skipped.
+pub fn verify_audit(path: &Path, signer: &Pubkey) -> Result<()> {
+    let entries = read_all(path)?;
+    let (mut failures, mut skipped) = (0, 0);
+    for entry in &entries {
+        if entry.audit_signature.is_none() && entry.signed_offline {
+            skipped += 1;
+            println!(
+                "  SKIPPED {} ({}): broadcast without the sender keypair, not audit-signed",
+                entry.signature, entry.timestamp
+            );
+            continue;
+        }
+        if entry.verify(signer) {
+            continue;
+        }
+        failures += 1;
+        let reason = if entry.audit_signature.is_none() {
+            "unsigned"
+        } else {
+            "signature does not verify"
+        };
+        println!(
+            "  FAILED {} ({}): {}",
+            entry.signature, entry.timestamp, reason
+        );
+    }
+    println!(
+        "Audit verification against {}: entries = {}, failed = {}, skipped = {}",
+        signer,
+        entries.len(),
+        failures,
+        skipped
+    );
+    if failures > 0 {
+        return Err(anyhow!(
+            "{} of {} history entries failed audit verification",
+            failures,
+            entries.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_archives_full_file_and_reads_across_archives() {
+        let dir = std::env::temp_dir().join(format!("aft-history-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+        let rotation = Some(Rotation {
+            max_bytes: 1,
+            keep_files: 2,
+        });
+
+        assert_eq!(last_sequence(&path).unwrap(), None);
+        for lamports in 1..=4 {
+            let mut entry = HistoryEntry::new(
+                format!("sig{}", lamports),
+                "sender".to_string(),
+                "receiver".to_string(),
+                lamports,
+                None,
+                None,
+            );
+            entry.sequence = Some(lamports);
+            append(&path, &entry, rotation).unwrap();
+        }
+
+        // Every append after the first rotated the previous line away; only 2 archives are kept
+        assert_eq!(archives(&path).unwrap().len(), 2);
+        let lamports: Vec<u64> = read_all(&path)
+            .unwrap()
+            .iter()
+            .map(|e| e.lamports)
+            .collect();
+        assert_eq!(lamports, vec![2, 3, 4]);
+        assert_eq!(last_sequence(&path).unwrap(), Some(4));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fee_projection_scales_recent_transfers_to_a_month() {
+        let now = SystemTime::now();
+        let entry = |days_ago: u64, lamports: u64, fee: Option<u64>| {
+            let mut entry = HistoryEntry::new(
+                "sig".to_string(),
+                "sender".to_string(),
+                "receiver".to_string(),
+                lamports,
+                fee,
+                None,
+            );
+            entry.timestamp = humantime::format_rfc3339_seconds(
+                now - Duration::from_secs(days_ago * 24 * 60 * 60),
+            )
+            .to_string();
+            entry
+        };
+        // Older than the window, and a failed transfer: both ignored
+        let mut failed = entry(4, 9_000, None);
+        failed.failure = Some("failed to land".to_string());
+        let mut entries = vec![entry(40, 1_000_000, Some(5000)), failed];
+        assert_eq!(fee_projection(&entries, now), None);
+
+        // Three transfers 5 days apart: two intervals of build-up in 10 days
+        entries.extend([
+            entry(12, 7_000, Some(5000)),
+            entry(7, 2_000, Some(7000)),
+            entry(2, 4_000, None),
+        ]);
+        let projection = fee_projection(&entries, now).unwrap();
+        assert_eq!(projection.transfers_per_month, 6.0);
+        assert_eq!(projection.average_fee_lamports, 6000);
+        assert_eq!(projection.fee_lamports_per_month, 36_000);
+        assert_eq!(projection.swept_lamports_per_month, 18_000);
+    }
+
+    #[test]
+    fn audit_signature_detects_tampering() {
+        let keypair = Keypair::new();
+        let mut entry = HistoryEntry::new(
+            "sig".to_string(),
+            keypair.pubkey().to_string(),
+            "receiver".to_string(),
+            5,
+            Some(5000),
+            Some(42),
+        );
+        entry.sign(Some(&keypair));
+
+        // Survives the round trip through a JSON line
+        let line = serde_json::to_string(&entry).unwrap();
+        let mut entry: HistoryEntry = serde_json::from_str(&line).unwrap();
+        assert!(entry.verify(&keypair.pubkey()));
+        assert!(!entry.verify(&Pubkey::new_unique()));
+
+        entry.lamports = 6;
+        assert!(!entry.verify(&keypair.pubkey()));
+        entry.audit_signature = None;
+        assert!(!entry.verify(&keypair.pubkey()));
+    }
+
+    #[test]
+    fn verify_audit_skips_offline_broadcasts_but_fails_unsigned_entries() {
+        let dir = std::env::temp_dir().join(format!("aft-audit-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+        let keypair = Keypair::new();
+        let entry = |signature: &str| {
+            HistoryEntry::new(
+                signature.to_string(),
+                keypair.pubkey().to_string(),
+                "receiver".to_string(),
+                5,
+                None,
+                None,
+            )
+        };
+
+        let mut signed = entry("signed");
+        signed.sign(Some(&keypair));
+        append(&path, &signed, None).unwrap();
+        let mut broadcast = entry("broadcast");
+        broadcast.sign(None);
+        append(&path, &broadcast, None).unwrap();
+        assert!(read_all(&path).unwrap()[1].signed_offline);
+        verify_audit(&path, &keypair.pubkey()).unwrap();
+
+        append(&path, &entry("unsigned"), None).unwrap();
+        assert!(verify_audit(&path, &keypair.pubkey()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Keypair file loading with specific, actionable errors for common misconfigurations.
+//!
+//! The file contents and decoded bytes are zeroized once parsed; so is a keypair read from
+//! Vault (`vault_sender_keypair`).
+
+use anyhow::{Result, anyhow};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::{fs, io::ErrorKind, path::Path};
+use zeroize::Zeroizing;
+
+/// Bytes in a keypair file: 32-byte secret key followed by the 32-byte public key.
+const KEYPAIR_LENGTH: usize = 64;
+
+/// Read a `solana-keygen` JSON keypair file. `what` names the keypair in error messages
+/// (e.g. "sender").
+pub fn load(path: &Path, what: &str) -> Result<Keypair> {
+    let text = Zeroizing::new(fs::read_to_string(path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "{} keypair file {} not found; check the path in the config",
+            what,
+            path.display()
+        ),
+        ErrorKind::PermissionDenied => anyhow!(
+            "permission denied reading {} keypair file {}; check its owner and mode against the service user",
+            what,
+            path.display()
+        ),
+        _ => anyhow!(
+            "reading {} keypair file {}: {}",
+            what,
+            path.display(),
+            e
+        ),
+    })?);
+    parse(&text, &format!("keypair file {}", path.display()), what)
+}
+
+/// Parse keypair JSON read from `source` (e.g. "keypair file /etc/aft/id.json"); `what` and
+/// `source` name the keypair in error messages.
+pub fn parse(text: &str, source: &str, what: &str) -> Result<Keypair> {
+    let bytes: Zeroizing<Vec<u8>> =
+        serde_json::from_str(text)
+            .map(Zeroizing::new)
+            .map_err(|e| {
+                anyhow!(
+                    "{} {} is not a JSON byte array as written by `solana-keygen new`: {}",
+                    what,
+                    source,
+                    e
+                )
+            })?;
+    if bytes.len() != KEYPAIR_LENGTH {
+        return Err(anyhow!(
+            "{} {} holds {} bytes, expected {}; the file is truncated or not a keypair",
+            what,
+            source,
+            bytes.len(),
+            KEYPAIR_LENGTH
+        ));
+    }
+    let keypair = Keypair::try_from(bytes.as_slice()).map_err(|e| {
+        anyhow!(
+            "{} {} is not a valid keypair (public key does not match secret key?): {}",
+            what,
+            source,
+            e
+        )
+    })?;
+    if keypair.pubkey() == Pubkey::default() {
+        return Err(anyhow!(
+            "{} {} has an all-zero public key; refusing to use it",
+            what,
+            source
+        ));
+    }
+    Ok(keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "aft-keypair-test-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn load_err(name: &str, contents: &str) -> String {
+        let path = write_temp(name, contents);
+        let err = load(&path, "sender").unwrap_err().to_string();
+        fs::remove_file(path).unwrap();
+        err
+    }
+
+    #[test]
+    fn valid_keypair_file_loads() {
+        let keypair = Keypair::new();
+        let path = write_temp("valid", &format!("{:?}", keypair.to_bytes().to_vec()));
+        assert_eq!(load(&path, "sender").unwrap().pubkey(), keypair.pubkey());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let err = load(Path::new("/nonexistent/id.json"), "sender")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not found"), "{}", err);
+    }
+
+    #[test]
+    fn malformed_and_truncated_files_are_reported() {
+        let err = load_err("malformed", "not json");
+        assert!(err.contains("not a JSON byte array"), "{}", err);
+
+        let err = load_err("truncated", "[1, 2, 3]");
+        assert!(err.contains("holds 3 bytes, expected 64"), "{}", err);
+    }
+}
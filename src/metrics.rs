@@ -0,0 +1,341 @@
+//! Prometheus metrics for the transfer daemon.
+//!
+//! Metrics are always recorded; they are only exposed over HTTP (`GET /metrics`) when
+//! `metrics_listen_addr` is configured, or pushed to a Pushgateway at the end of a `--once` run
+//! when `prometheus_pushgateway` is.
+
+use anyhow::{Context, Result, anyhow};
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, Registry, TextEncoder,
+    core::Collector,
+};
+use std::fmt::Write;
+use std::{collections::HashMap, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tracing::{info, warn};
+
+use crate::USER_AGENT;
+
+/// Pushgateway job name; the deployment name is added as a second grouping label.
+const PUSH_JOB: &str = env!("CARGO_PKG_NAME");
+
+/// Buckets (in SOL) for observed sender balances. Dense around the default 7 SOL threshold.
+const BALANCE_BUCKETS_SOL: &[f64] = &[
+    0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 7.5, 8.0, 9.0, 10.0, 15.0, 20.0, 50.0, 100.0,
+];
+
+/// Buckets (in SOL) for confirmed transfer amounts.
+const TRANSFER_BUCKETS_SOL: &[f64] = &[
+    0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0,
+];
+
+/// Buckets (in seconds) for `--profile` RPC call and cycle durations. Confirmations take about
+/// 15s at finalized commitment.
+const DURATION_BUCKETS_SECONDS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0,
+];
+
+/// All metrics exported by the daemon, registered on a private registry.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Last observed sender balance in SOL.
+    pub balance_sol: Gauge,
+    /// Distribution of observed sender balances in SOL.
+    pub balance_sol_observed: Histogram,
+    /// Distribution of confirmed transfer amounts in SOL.
+    pub transfer_sol: Histogram,
+    /// Number of confirmed transfers.
+    pub transfers_total: IntCounter,
+    /// Threshold in effect for the last cycle, in SOL.
+    pub threshold_sol: Gauge,
+    /// Raw excess above threshold computed in the last cycle, in SOL.
+    pub excess_sol: Gauge,
+    /// Amount transferred in the last cycle minus the fee paid for it, in SOL (0 if none).
+    pub transfer_net_sol: Gauge,
+    /// Number of poll cycles that took longer than the poll interval.
+    pub cycle_overrun_total: IntCounter,
+    /// Duration of each RPC call by method, in seconds (`--profile` only).
+    pub rpc_phase_seconds: HistogramVec,
+    /// Duration of whole poll cycles in seconds (`--profile` only).
+    pub cycle_seconds: Histogram,
+}
+
+impl Metrics {
+    /// Metrics registry whose every metric carries a `deployment` label.
+    pub fn new(deployment: &str) -> Result<Self> {
+        let labels = HashMap::from([("deployment".to_string(), deployment.to_string())]);
+        let registry = Registry::new_custom(None, Some(labels))?;
+
+        let balance_sol = Gauge::new("aft_balance_sol", "Last observed sender balance in SOL")?;
+        let balance_sol_observed = Histogram::with_opts(
+            HistogramOpts::new(
+                "aft_balance_sol_observed",
+                "Distribution of observed sender balances in SOL",
+            )
+            .buckets(BALANCE_BUCKETS_SOL.to_vec()),
+        )?;
+        let transfer_sol = Histogram::with_opts(
+            HistogramOpts::new(
+                "aft_transfer_sol",
+                "Distribution of confirmed transfer amounts in SOL",
+            )
+            .buckets(TRANSFER_BUCKETS_SOL.to_vec()),
+        )?;
+        let transfers_total =
+            IntCounter::new("aft_transfers_total", "Number of confirmed transfers")?;
+        let threshold_sol = Gauge::new(
+            "aft_threshold_sol",
+            "Threshold in effect for the last cycle in SOL",
+        )?;
+        let excess_sol = Gauge::new(
+            "aft_excess_sol",
+            "Raw excess above threshold computed in the last cycle in SOL",
+        )?;
+        let transfer_net_sol = Gauge::new(
+            "aft_transfer_net_sol",
+            "Amount transferred in the last cycle minus the fees the sender paid for it in SOL",
+        )?;
+
+        let cycle_overrun_total = IntCounter::new(
+            "aft_cycle_overrun_total",
+            "Number of poll cycles that took longer than the poll interval",
+        )?;
+
+        let rpc_phase_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "aft_rpc_phase_seconds",
+                "Duration of each RPC call by method in seconds (--profile only)",
+            )
+            .buckets(DURATION_BUCKETS_SECONDS.to_vec()),
+            &["method"],
+        )?;
+        let cycle_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "aft_cycle_seconds",
+                "Duration of whole poll cycles in seconds (--profile only)",
+            )
+            .buckets(DURATION_BUCKETS_SECONDS.to_vec()),
+        )?;
+
+        registry.register(Box::new(balance_sol.clone()))?;
+        registry.register(Box::new(balance_sol_observed.clone()))?;
+        registry.register(Box::new(transfer_sol.clone()))?;
+        registry.register(Box::new(transfers_total.clone()))?;
+        registry.register(Box::new(threshold_sol.clone()))?;
+        registry.register(Box::new(excess_sol.clone()))?;
+        registry.register(Box::new(transfer_net_sol.clone()))?;
+        registry.register(Box::new(cycle_overrun_total.clone()))?;
+        registry.register(Box::new(rpc_phase_seconds.clone()))?;
+        registry.register(Box::new(cycle_seconds.clone()))?;
+
+        Ok(Self {
+            registry,
+            balance_sol,
+            balance_sol_observed,
+            transfer_sol,
+            transfers_total,
+            threshold_sol,
+            excess_sol,
+            transfer_net_sol,
+            cycle_overrun_total,
+            rpc_phase_seconds,
+            cycle_seconds,
+        })
+    }
+
+    /// Record a balance reading.
+    pub fn observe_balance(&self, balance_sol: f64) {
+        self.balance_sol.set(balance_sol);
+        self.balance_sol_observed.observe(balance_sol);
+    }
+
+    /// Record the threshold and raw excess of a cycle; clears the net transfer gauge until a
+    /// transfer confirms in this cycle.
+    pub fn observe_cycle(&self, threshold_sol: f64, excess_sol: f64) {
+        self.threshold_sol.set(threshold_sol);
+        self.excess_sol.set(excess_sol);
+        self.transfer_net_sol.set(0.0);
+    }
+
+    /// Record a confirmed transfer and the fee the sender paid for it (0 when a fee payer did).
+    /// The net transfer gauge sums every transfer (chunk, tier) of the cycle.
+    pub fn observe_transfer(&self, amount_sol: f64, sender_fee_sol: f64) {
+        self.transfer_sol.observe(amount_sol);
+        self.transfers_total.inc();
+        self.transfer_net_sol.add(amount_sol - sender_fee_sol);
+    }
+
+    /// The `# HELP` and `# TYPE` lines of every metric, as in the exposition format, for
+    /// `--print-metrics`. Unlike `render`, this includes metrics that have no samples yet.
+    pub fn definitions(&self) -> String {
+        let collectors: [(&dyn Collector, &str); 10] = [
+            (&self.balance_sol, "gauge"),
+            (&self.balance_sol_observed, "histogram"),
+            (&self.transfer_sol, "histogram"),
+            (&self.transfers_total, "counter"),
+            (&self.threshold_sol, "gauge"),
+            (&self.excess_sol, "gauge"),
+            (&self.transfer_net_sol, "gauge"),
+            (&self.cycle_overrun_total, "counter"),
+            (&self.rpc_phase_seconds, "histogram"),
+            (&self.cycle_seconds, "histogram"),
+        ];
+        let mut out = String::new();
+        for (collector, kind) in collectors {
+            for desc in collector.desc() {
+                let _ = writeln!(out, "# HELP {} {}", desc.fq_name, desc.help);
+                let _ = writeln!(out, "# TYPE {} {}", desc.fq_name, kind);
+            }
+        }
+        out
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String> {
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+}
+
+/// Pushgateway URL for this daemon's metric group. Path segments are percent-encoded, so any
+/// deployment name is safe to use.
+fn push_url(gateway: &str, deployment: &str) -> Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(gateway)
+        .with_context(|| format!("parsing prometheus_pushgateway {:?}", gateway))?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("prometheus_pushgateway {:?} cannot be a base URL", gateway))?
+        .pop_if_empty()
+        .extend(["metrics", "job", PUSH_JOB, "deployment", deployment]);
+    Ok(url)
+}
+
+/// Replace this deployment's metric group on the Pushgateway at `gateway` with the current
+/// metrics (`PUT`), grouped by job and `deployment` so hosts don't overwrite each other.
+pub async fn push(
+    gateway: &str,
+    deployment: &str,
+    metrics: &Metrics,
+    timeout: Duration,
+) -> Result<()> {
+    let url = push_url(gateway, deployment)?;
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .build()
+        .context("building HTTP client")?;
+    let resp = client
+        .put(url.clone())
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(metrics.render()?)
+        .send()
+        .await
+        .with_context(|| format!("pushing metrics to {}", url))?;
+    if !resp.status().is_success() {
+        return Err(anyhow!(
+            "pushgateway {} returned {}: {}",
+            url,
+            resp.status(),
+            resp.text().await.unwrap_or_default()
+        ));
+    }
+    info!("Pushed metrics to {}", url);
+    Ok(())
+}
+
+/// Serve `GET /metrics` on `addr` until the process exits.
+pub async fn serve(addr: String, metrics: Metrics) -> Result<()> {
+    let listener = TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("binding metrics listener on {}", addr))?;
+    info!("Serving metrics on http://{}/metrics", addr);
+
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Failed to accept metrics connection: {}", e);
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            // Only the request line matters; anything beyond the first read is ignored.
+            let mut buf = [0u8; 1024];
+            let n = match stream.read(&mut buf).await {
+                Ok(n) => n,
+                Err(e) => {
+                    warn!("Failed to read metrics request from {}: {}", peer, e);
+                    return;
+                }
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let path = request.split_whitespace().nth(1).unwrap_or("");
+
+            let response = match (path, metrics.render()) {
+                ("/metrics", Ok(body)) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                ("/metrics", Err(e)) => {
+                    warn!("Failed to render metrics: {}", e);
+                    "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+            };
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                warn!("Failed to write metrics response to {}: {}", peer, e);
+            }
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_url_groups_by_job_and_encoded_deployment() {
+        assert_eq!(
+            push_url("http://pushgateway:9091/", "validator 07/a")
+                .unwrap()
+                .as_str(),
+            "http://pushgateway:9091/metrics/job/automated-fund-transfer/deployment/validator%2007%2Fa"
+        );
+        assert!(push_url("not a url", "host").is_err());
+    }
+
+    #[test]
+    fn net_transfer_sums_the_cycle() {
+        let metrics = Metrics::new("test").unwrap();
+        metrics.transfer_net_sol.set(5.0);
+        metrics.observe_cycle(7.0, 2.0);
+        metrics.observe_transfer(0.75, 0.25);
+        metrics.observe_transfer(1.25, 0.0);
+        assert_eq!(metrics.transfer_net_sol.get(), 1.75);
+    }
+
+    #[test]
+    fn definitions_list_every_registered_metric() {
+        let metrics = Metrics::new("test").unwrap();
+        let definitions = metrics.definitions();
+        assert!(definitions.contains(
+            "# HELP aft_transfers_total Number of confirmed transfers\n# TYPE aft_transfers_total counter\n"
+        ));
+        // Also the labelled histogram that has no samples until --profile records one
+        assert!(definitions.contains("# TYPE aft_rpc_phase_seconds histogram\n"));
+        let types = definitions
+            .lines()
+            .filter(|l| l.starts_with("# TYPE"))
+            .count();
+        assert_eq!(types, metrics.registry.gather().len() + 1);
+    }
+}
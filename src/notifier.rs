@@ -0,0 +1,375 @@
+//! Slack webhook notifications sent over one pooled HTTP client, optionally throttled per
+//! notification kind and capped per UTC day.
+
+use anyhow::{Context, Result, anyhow};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{info, warn};
+
+use crate::USER_AGENT;
+
+/// What a notification is about; throttling applies to each kind separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Transfer,
+    KeepAlive,
+    KeepAliveFailed,
+    Delinquent,
+    ReceiverBalanceHigh,
+    /// The receiver failed the allowlist or vote account check.
+    ReceiverRefused,
+    /// A transfer failed the same way `repeated_failure_limit` times in a row.
+    RepeatedFailure,
+    /// The sender balance read zero after a nonzero reading.
+    BalanceZero,
+    /// A transfer above `approval_required_above_sol` awaits manual approval.
+    ApprovalRequest,
+    /// The node's preflight simulation rejected a transfer; nothing was sent.
+    PreflightFailed,
+    /// A submitted transfer failed or could not be confirmed and may still land.
+    SubmissionFailed,
+    /// A transfer's blockhash expired on every one of `max_send_attempts` sends.
+    LandingFailed,
+    /// More transfers in an hour than `max_transfers_per_hour`.
+    ExcessiveTransfers,
+    /// No sweep for `dry_spell_alert_multiple` times the average interval between sweeps.
+    DrySpell,
+    /// This instance was promoted to or demoted from holding the failover lock.
+    Failover,
+    /// A config file changed on disk since startup (`config_watch_interval_seconds`).
+    ConfigChanged,
+    TaskFailure,
+}
+
+impl Kind {
+    /// Critical notifications always go out, even once the daily cap is reached.
+    fn is_critical(self) -> bool {
+        matches!(
+            self,
+            Kind::ReceiverRefused
+                | Kind::RepeatedFailure
+                | Kind::BalanceZero
+                | Kind::ApprovalRequest
+                | Kind::SubmissionFailed
+                | Kind::LandingFailed
+                | Kind::ExcessiveTransfers
+                | Kind::Failover
+                | Kind::ConfigChanged
+                | Kind::TaskFailure
+        )
+    }
+}
+
+/// Per-kind throttling state: when the last notification went out and how many were
+/// suppressed since.
+#[derive(Default)]
+struct Throttle {
+    last_sent: HashMap<Kind, Instant>,
+    suppressed: HashMap<Kind, u32>,
+    /// UTC day (days since the Unix epoch) that `sent_today` counts.
+    day: u64,
+    /// Notifications sent to the channel on `day`.
+    sent_today: u32,
+}
+
+impl Throttle {
+    /// The text to send now, with the suppressed count appended, or `None` if a notification of
+    /// this kind went out less than `min_interval` ago.
+    fn admit(&mut self, kind: Kind, text: &str, min_interval: Duration) -> Option<String> {
+        let now = Instant::now();
+        if self
+            .last_sent
+            .get(&kind)
+            .is_some_and(|last| now.duration_since(*last) < min_interval)
+        {
+            *self.suppressed.entry(kind).or_default() += 1;
+            return None;
+        }
+        self.last_sent.insert(kind, now);
+        Some(match self.suppressed.remove(&kind) {
+            Some(n) => format!("{}\n...and {} more similar alerts suppressed", text, n),
+            None => text.to_string(),
+        })
+    }
+
+    /// Whether the daily cap of `max_per_day` keeps a `kind` notification from going out on UTC
+    /// day `today`, without counting it. Logs when a new day resets the cap.
+    fn capped(&mut self, kind: Kind, max_per_day: u32, today: u64) -> bool {
+        if today != self.day {
+            if self.sent_today >= max_per_day {
+                info!("Notification daily cap reset for the new UTC day: channel = slack");
+            }
+            self.day = today;
+            self.sent_today = 0;
+        }
+        self.sent_today >= max_per_day && !kind.is_critical()
+    }
+
+    /// Count a notification against the daily cap; logs when it caps the channel.
+    fn count_daily(&mut self, max_per_day: u32) {
+        self.sent_today += 1;
+        if self.sent_today == max_per_day {
+            warn!(
+                "Notification channel capped for the rest of the UTC day; only critical alerts go out: channel = slack, max_notifications_per_day = {}",
+                max_per_day
+            );
+        }
+    }
+
+    /// The text of a `kind` notification to send now under both the throttle and the daily cap,
+    /// or why it is suppressed. The cap is checked first: a capped notification leaves the
+    /// throttle's last-sent time alone and counts as suppressed, so the next admitted one reports
+    /// it.
+    fn admit_all(
+        &mut self,
+        kind: Kind,
+        text: &str,
+        min_interval: Option<Duration>,
+        max_per_day: Option<u32>,
+        today: u64,
+    ) -> Result<String, Suppression> {
+        if let Some(max_per_day) = max_per_day {
+            if self.capped(kind, max_per_day, today) {
+                if min_interval.is_some() {
+                    *self.suppressed.entry(kind).or_default() += 1;
+                }
+                return Err(Suppression::DailyCap);
+            }
+        }
+        let text = match min_interval {
+            Some(min_interval) => self
+                .admit(kind, text, min_interval)
+                .ok_or(Suppression::Throttling)?,
+            None => text.to_string(),
+        };
+        if let Some(max_per_day) = max_per_day {
+            self.count_daily(max_per_day);
+        }
+        Ok(text)
+    }
+}
+
+/// Why a notification was not sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suppression {
+    Throttling,
+    DailyCap,
+}
+
+/// Days since the Unix epoch, i.e. the current UTC day.
+fn utc_day() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / (24 * 60 * 60)
+}
+
+/// The notification channel of the daemon. Cheap to clone: clones share the same connection
+/// pool, so TLS sessions to the webhook host are reused across notifications.
+#[derive(Clone)]
+pub struct Notifier {
+    /// `None` if no HTTP client could be built; notifications are then unavailable.
+    client: Option<reqwest::Client>,
+    webhook: Option<String>,
+    enabled: bool,
+    /// Minimum interval between notifications of the same kind; no throttling when `None`.
+    min_interval: Option<Duration>,
+    /// Most notifications sent to the channel per UTC day, critical ones excepted; no cap
+    /// when `None`.
+    max_per_day: Option<u32>,
+    /// Shared by all clones so throttling is global to the process.
+    throttle: Arc<Mutex<Throttle>>,
+    /// Deployment name prefixed to every notification.
+    deployment: String,
+}
+
+impl Notifier {
+    /// Build the notifier and its HTTP client. A client that cannot be built (e.g. no usable TLS
+    /// backend) is logged and leaves the daemon running without notifications.
+    pub fn new(
+        webhook: Option<String>,
+        enabled: bool,
+        timeout: Duration,
+        min_interval: Option<Duration>,
+        max_per_day: Option<u32>,
+        deployment: &str,
+    ) -> Self {
+        let client = match build_http_client(timeout) {
+            Ok(client) => Some(client),
+            Err(e) => {
+                warn!(
+                    "Notifications unavailable, HTTP client could not be initialized: {:#}",
+                    e
+                );
+                None
+            }
+        };
+        Self {
+            client,
+            webhook,
+            enabled,
+            min_interval,
+            max_per_day,
+            throttle: Arc::default(),
+            deployment: deployment.to_string(),
+        }
+    }
+
+    /// Whether notifications are enabled by config.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Names of the channels notifications go to, for the startup banner.
+    pub fn channels(&self) -> Vec<&'static str> {
+        if self.enabled && self.client.is_some() && self.webhook.is_some() {
+            vec!["slack"]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Best-effort notification to the configured channel (if any and enabled); failures are
+    /// only logged. Within the throttling interval of the last `kind` notification it is
+    /// suppressed and counted in the next one instead; past the daily cap it is dropped unless
+    /// critical.
+    pub async fn notify(&self, kind: Kind, text: &str) {
+        if !self.enabled {
+            return;
+        }
+        let admitted = self.throttle.lock().unwrap().admit_all(
+            kind,
+            text,
+            self.min_interval,
+            self.max_per_day,
+            utc_day(),
+        );
+        let text = match admitted {
+            Ok(text) => text,
+            Err(Suppression::Throttling) => {
+                info!("Notification suppressed by throttling: kind = {:?}", kind);
+                return;
+            }
+            Err(Suppression::DailyCap) => {
+                info!("Notification suppressed by daily cap: kind = {:?}", kind);
+                return;
+            }
+        };
+        let text = format!("[{}] {}", self.deployment, text);
+        if let (Some(client), Some(webhook)) = (&self.client, &self.webhook) {
+            match send_slack(client, webhook, &text).await {
+                Ok(_) => info!("Slack notification sent"),
+                Err(e) => warn!("Slack notification failed: {}", e),
+            }
+        }
+    }
+
+    /// Check that the webhook host is reachable without sending a notification. Any HTTP
+    /// response (even 4xx/405 from endpoints that reject HEAD) counts as reachable; DNS, connect
+    /// and timeout failures do not. `None` if there is no webhook or HTTP client to probe with.
+    pub async fn probe(&self) -> Option<Result<reqwest::StatusCode>> {
+        let (client, webhook) = (self.client.as_ref()?, self.webhook.as_ref()?);
+        Some(
+            client
+                .head(webhook)
+                .send()
+                .await
+                .map(|resp| resp.status())
+                .map_err(Into::into),
+        )
+    }
+}
+
+/// Build the HTTP client shared by all webhook calls. Fails if no TLS backend is usable.
+fn build_http_client(timeout: Duration) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .build()
+        .context("building HTTP client")
+}
+
+async fn send_slack(client: &reqwest::Client, webhook: &str, text: &str) -> Result<()> {
+    let payload = serde_json::json!({ "text": text });
+    let resp = match client.post(webhook).json(&payload).send().await {
+        Ok(resp) => resp,
+        Err(e) if e.is_timeout() => return Err(anyhow!("slack webhook timed out")),
+        Err(e) => return Err(e.into()),
+    };
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("slack webhook returned status {}", resp.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_coalesces_suppressed_alerts_per_kind() {
+        let mut throttle = Throttle::default();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            throttle.admit(Kind::Delinquent, "a", hour).as_deref(),
+            Some("a")
+        );
+        assert_eq!(throttle.admit(Kind::Delinquent, "b", hour), None);
+        assert_eq!(throttle.admit(Kind::Delinquent, "c", hour), None);
+        // Other kinds are throttled separately
+        assert!(throttle.admit(Kind::Transfer, "t", hour).is_some());
+        // Once the interval has passed, the suppressed count rides along
+        assert_eq!(
+            throttle
+                .admit(Kind::Delinquent, "d", Duration::ZERO)
+                .as_deref(),
+            Some("d\n...and 2 more similar alerts suppressed")
+        );
+    }
+
+    #[test]
+    fn daily_cap_lets_only_critical_alerts_through_until_next_day() {
+        let mut throttle = Throttle::default();
+        let mut admit = |kind, today| throttle.admit_all(kind, "", None, Some(2), today).is_ok();
+        assert!(admit(Kind::Transfer, 100));
+        assert!(admit(Kind::Delinquent, 100));
+        assert!(!admit(Kind::Transfer, 100));
+        assert!(admit(Kind::RepeatedFailure, 100));
+        assert!(admit(Kind::Transfer, 101));
+    }
+
+    #[test]
+    fn capped_alerts_count_as_suppressed_without_resetting_the_throttle() {
+        let mut throttle = Throttle::default();
+        let (interval, cap) = (Some(Duration::ZERO), Some(1));
+        assert_eq!(
+            throttle.admit_all(Kind::Delinquent, "a", interval, cap, 100),
+            Ok("a".to_string())
+        );
+        assert_eq!(
+            throttle.admit_all(Kind::Delinquent, "b", interval, cap, 100),
+            Err(Suppression::DailyCap)
+        );
+        assert_eq!(
+            throttle.admit_all(Kind::Delinquent, "c", interval, cap, 101),
+            Ok("c\n...and 1 more similar alerts suppressed".to_string())
+        );
+        // Throttled alerts do not use up the cap
+        let hour = Some(Duration::from_secs(3600));
+        assert_eq!(
+            throttle.admit_all(Kind::Delinquent, "d", hour, Some(2), 101),
+            Err(Suppression::Throttling)
+        );
+        assert!(
+            throttle
+                .admit_all(Kind::Transfer, "t", None, Some(2), 101)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Priority fees aimed at a confirmation target (`target_confirmation_slots`).
+//!
+//! Before each transfer the recent prioritization fees paid for the sender's and receiver's
+//! accounts are sampled, and a percentile is picked by the target: the tighter the target, the
+//! higher the percentile. The result is scaled by an adjustment learned from the sweeps so far,
+//! raised after one confirmed slower than the target and lowered after one confirmed on time,
+//! then capped at `max_compute_unit_price`.
+
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+
+/// Compute units requested by a priority-fee transfer; a system transfer and a sweep memo use a
+/// few thousand, and the priority fee is charged on the limit, not on the units used.
+pub const COMPUTE_UNIT_LIMIT: u32 = 50_000;
+
+/// Default `max_compute_unit_price`, in micro-lamports per compute unit: at most 0.00005 SOL of
+/// priority fee per transfer at `COMPUTE_UNIT_LIMIT`.
+pub const DEFAULT_MAX_COMPUTE_UNIT_PRICE: u64 = 1_000_000;
+
+/// Factor the adjustment is raised by after a sweep missed the target.
+const RAISE: f64 = 1.5;
+/// Factor the adjustment is lowered by after a sweep met the target.
+const LOWER: f64 = 0.9;
+/// Bounds of the adjustment.
+const ADJUSTMENT_RANGE: (f64, f64) = (0.25, 16.0);
+
+/// Fee selection for a confirmation target, with its feedback from past sweeps.
+#[derive(Debug, Clone)]
+pub struct PriorityFee {
+    pub target_slots: u64,
+    max_price: u64,
+    /// Multiplier on the sampled fee.
+    pub adjustment: f64,
+}
+
+impl PriorityFee {
+    pub fn new(target_slots: u64, max_price: u64) -> Self {
+        Self {
+            target_slots,
+            max_price,
+            adjustment: 1.0,
+        }
+    }
+
+    /// Compute unit price in micro-lamports for the next transfer, from the prioritization fees
+    /// of recent slots. At least 1, so a missed target can raise it even when recent slots paid
+    /// no priority fee.
+    pub fn price(&self, recent_fees: &[u64]) -> u64 {
+        let mut fees = recent_fees.to_vec();
+        fees.sort_unstable();
+        let sampled = match fees.len() {
+            0 => 0,
+            n => fees[((n - 1) as f64 * self.percentile() / 100.0).round() as usize],
+        };
+        ((sampled.max(1) as f64 * self.adjustment).round() as u64).clamp(1, self.max_price)
+    }
+
+    /// Percentile of the recent fees aimed at: 90 for confirmation in the next slot, down to 25
+    /// for targets of 5 slots and more.
+    fn percentile(&self) -> f64 {
+        match self.target_slots {
+            0 | 1 => 90.0,
+            2 => 75.0,
+            3 | 4 => 50.0,
+            _ => 25.0,
+        }
+    }
+
+    /// Feed back that a sweep confirmed `slots` slots after it was sent.
+    pub fn observe(&mut self, slots: u64) {
+        let factor = if slots > self.target_slots {
+            RAISE
+        } else {
+            LOWER
+        };
+        self.adjustment = (self.adjustment * factor).clamp(ADJUSTMENT_RANGE.0, ADJUSTMENT_RANGE.1);
+    }
+}
+
+/// Compute budget instructions setting `COMPUTE_UNIT_LIMIT` and a compute unit `price`.
+pub fn instructions(price: u64) -> [Instruction; 2] {
+    [
+        ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNIT_LIMIT),
+        ComputeBudgetInstruction::set_compute_unit_price(price),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_follows_the_target_and_the_observed_confirmations() {
+        let fees: Vec<u64> = (1..=101).map(|i| i * 100).collect();
+        assert_eq!(PriorityFee::new(1, 1_000_000).price(&fees), 9_100);
+        assert_eq!(PriorityFee::new(3, 1_000_000).price(&fees), 5_100);
+        assert_eq!(PriorityFee::new(3, 1_000).price(&fees), 1_000);
+        // No recent priority fees: the smallest price, raised once a sweep is late
+        let mut fee = PriorityFee::new(2, 1_000_000);
+        assert_eq!(fee.price(&[]), 1);
+        for _ in 0..4 {
+            fee.observe(5);
+        }
+        assert_eq!(fee.price(&[0, 0]), 5);
+        assert_eq!(fee.price(&fees), 38_475);
+        fee.observe(1);
+        assert!((fee.adjustment - 4.55625).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,59 @@
+//! Optional masking of account addresses and signatures in log output (`--redact-addresses`).
+//!
+//! Only logs are affected; notifications go to private channels and keep full addresses.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn on redaction for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// An address or signature to be logged; masked when redaction is enabled.
+pub struct Redacted<T>(T);
+
+/// Wrap an address or signature for logging.
+pub fn addr<T: fmt::Display>(value: T) -> Redacted<T> {
+    Redacted(value)
+}
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if ENABLED.load(Ordering::Relaxed) {
+            f.write_str(&mask(&self.0.to_string()))
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// Keep only the first and last 4 characters (`H7o1…hqhC`); shorter values are fully masked.
+fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "…".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_keeps_first_and_last_four_characters() {
+        assert_eq!(
+            mask("H7o1WSG4dAUFihPUkz1eHiW6L19gb71pEg4WnVpGhqhC"),
+            "H7o1…hqhC"
+        );
+        assert_eq!(mask("12345678"), "…");
+        assert_eq!(mask(""), "…");
+    }
+}
@@ -0,0 +1,78 @@
+//! RPC calls bounded by the deadline of the poll cycle in progress (`cycle_budget_seconds`).
+//!
+//! The clients are blocking and their request timeout is fixed when they are built, so a fixed
+//! timeout cannot shrink as the cycle runs. Instead every request goes through a sender that
+//! reads the shared deadline and gives up once it passes, however long the transport would have
+//! waited.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+/// Deadline of the current poll cycle, shared between the daemon and its RPC clients; `None`
+/// outside a cycle or without a cycle budget.
+#[derive(Debug, Clone, Default)]
+pub struct Deadline(Arc<Mutex<Option<Instant>>>);
+
+impl Deadline {
+    pub fn set(&self, deadline: Option<Instant>) {
+        *self.0.lock().unwrap() = deadline;
+    }
+
+    /// Time left until the deadline (zero once it passed); `None` if there is none.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0
+            .lock()
+            .unwrap()
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// Wraps the transport of an `RpcClient` so no request outlives the cycle deadline.
+pub struct DeadlineSender<S> {
+    inner: S,
+    deadline: Deadline,
+}
+
+impl<S> DeadlineSender<S> {
+    pub fn new(inner: S, deadline: Deadline) -> Self {
+        Self { inner, deadline }
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for DeadlineSender<S> {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let Some(remaining) = self.deadline.remaining() else {
+            return self.inner.send(request, params).await;
+        };
+        let exhausted = || {
+            ClientError::from(ClientErrorKind::Custom(format!(
+                "cycle budget exhausted: {} abandoned",
+                request
+            )))
+        };
+        if remaining.is_zero() {
+            return Err(exhausted());
+        }
+        tokio::time::timeout(remaining, self.inner.send(request, params))
+            .await
+            .map_err(|_| exhausted())?
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
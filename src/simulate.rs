@@ -0,0 +1,96 @@
+//! `--simulate-balances`: replay a scripted sequence of sender balances through the transfer
+//! decision logic, without network access, and print what the daemon would have done.
+//!
+//! Scenario file (TOML), one `[[step]]` per poll cycle:
+//!
+//! ```toml
+//! [[step]]
+//! balance_sol = 6.5
+//!
+//! [[step]]
+//! balance_sol = 9.2
+//! after_seconds = 3600   # time since the previous step; defaults to the next poll interval
+//! ```
+//!
+//! Other RPC reads (receiver balance, epoch, vote and stake accounts) get fixed placeholder
+//! values, so decisions depending on them are only indicative.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{fs, path::Path, time::Duration};
+
+use crate::{Rounding, amount, daemon::CycleOutcome, daemon::Daemon, sol_to_lamports};
+
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    #[serde(rename = "step", default)]
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Step {
+    balance_sol: f64,
+    after_seconds: Option<u64>,
+}
+
+fn parse_scenario(text: &str) -> Result<Scenario> {
+    Ok(toml::from_str(text)?)
+}
+
+/// Run every step of the scenario file through `daemon` (in dry-run mode) and print one
+/// decision per step to stdout.
+pub async fn run(daemon: &mut Daemon, path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("reading scenario file {}", path.display()))?;
+    let scenario = parse_scenario(&text)
+        .with_context(|| format!("parsing scenario file {}", path.display()))?;
+    daemon.set_dry_run(true);
+
+    println!("Simulating {} balance steps", scenario.steps.len());
+    let mut elapsed = Duration::ZERO;
+    let mut transferred = 0;
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let advance = match (i, step.after_seconds) {
+            (0, _) => Duration::ZERO,
+            (_, Some(seconds)) => Duration::from_secs(seconds),
+            (_, None) => daemon.next_poll_interval(),
+        };
+        elapsed += advance;
+        let balance = sol_to_lamports(step.balance_sol, Rounding::Floor);
+        let decision = match daemon.simulate_cycle(balance, advance).await {
+            CycleOutcome::Idle => "idle".to_string(),
+            CycleOutcome::Deferred => "deferred (see log)".to_string(),
+            CycleOutcome::WouldTransfer(lamports) => {
+                transferred += lamports;
+                format!("transfer {} SOL", amount::sol(lamports))
+            }
+            outcome => format!("{:?}", outcome),
+        };
+        println!(
+            "  step {}: t+{}s balance_sol = {} -> {}",
+            i + 1,
+            elapsed.as_secs(),
+            step.balance_sol,
+            decision
+        );
+    }
+    println!("Would have transferred {} SOL", amount::sol(transferred));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenario_steps_are_parsed() {
+        let scenario = parse_scenario(
+            "[[step]]\nbalance_sol = 6.5\n\n[[step]]\nbalance_sol = 9.2\nafter_seconds = 3600\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.steps.len(), 2);
+        assert_eq!(scenario.steps[0].after_seconds, None);
+        assert_eq!(scenario.steps[1].balance_sol, 9.2);
+        assert_eq!(scenario.steps[1].after_seconds, Some(3600));
+    }
+}
@@ -0,0 +1,83 @@
+//! Stake activation of monitored stake accounts, computed client-side from the account data and
+//! the StakeHistory sysvar (the `getStakeActivation` RPC method is no longer served).
+
+use anyhow::{Context, Result};
+use solana_sdk::{clock::Epoch, pubkey, pubkey::Pubkey};
+use solana_stake_interface::{stake_history::StakeHistory, state::StakeStateV2};
+
+/// Feature gate that lowered the stake warmup/cooldown rate from 25% to 9% per epoch.
+pub const REDUCE_STAKE_WARMUP_COOLDOWN: Pubkey =
+    pubkey!("GwtDQBghCTBgmX2cpEGNPxTEBUTQRaDMGTr5qychdGMj");
+
+/// Parse the StakeHistory sysvar account data.
+pub fn parse_stake_history(data: &[u8]) -> Result<StakeHistory> {
+    bincode::deserialize(data).context("parsing StakeHistory sysvar")
+}
+
+/// Lamports of the stake account (`data`) still deactivating at `epoch`. Accounts without a
+/// delegation have none.
+pub fn deactivating_lamports(
+    data: &[u8],
+    epoch: Epoch,
+    history: &StakeHistory,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> Result<u64> {
+    let state: StakeStateV2 = bincode::deserialize(data).context("parsing stake account")?;
+    Ok(state.delegation().map_or(0, |delegation| {
+        delegation
+            .stake_activating_and_deactivating(epoch, history, new_rate_activation_epoch)
+            .deactivating
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_stake_interface::{
+        stake_flags::StakeFlags,
+        state::{Delegation, Meta, Stake},
+    };
+
+    fn stake_account(deactivation_epoch: Epoch) -> Vec<u8> {
+        let stake = Stake {
+            delegation: Delegation {
+                voter_pubkey: Pubkey::new_unique(),
+                stake: 1_000,
+                // Bootstrap stake: fully effective from the start
+                activation_epoch: u64::MAX,
+                deactivation_epoch,
+                ..Delegation::default()
+            },
+            credits_observed: 0,
+        };
+        bincode::serialize(&StakeStateV2::Stake(
+            Meta::default(),
+            stake,
+            StakeFlags::empty(),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn stake_deactivating_this_epoch_is_counted() {
+        let history = StakeHistory::default();
+        assert_eq!(
+            deactivating_lamports(&stake_account(10), 10, &history, None).unwrap(),
+            1_000
+        );
+    }
+
+    #[test]
+    fn active_or_undelegated_stake_is_not_deactivating() {
+        let history = StakeHistory::default();
+        assert_eq!(
+            deactivating_lamports(&stake_account(u64::MAX), 10, &history, None).unwrap(),
+            0
+        );
+        let undelegated = bincode::serialize(&StakeStateV2::Initialized(Meta::default())).unwrap();
+        assert_eq!(
+            deactivating_lamports(&undelegated, 10, &history, None).unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Small JSON state file persisted across restarts.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fs, io::ErrorKind, path::Path};
+use tracing::warn;
+
+/// Most recent balance samples kept in the state file.
+pub const MAX_BALANCE_SAMPLES: usize = 16;
+
+/// Most recent sweep times kept in the state file.
+pub const MAX_TRANSFER_TIMES: usize = 16;
+
+/// Everything the daemon remembers across restarts. Unknown or missing fields fall back to
+/// their defaults so older state files keep loading.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Last epoch in which a sweep was made (`sweep_per_epoch` mode).
+    pub last_swept_epoch: Option<u64>,
+    /// Receiver that replaces `receiver_pubkey`, set by `migrate-receiver --confirm`.
+    pub receiver_override: Option<String>,
+    /// Receiver that got a confirmed `migrate-receiver` test transfer and awaits `--confirm`.
+    pub pending_receiver: Option<String>,
+    /// Sequence number of the last confirmed sweep (0 before the first).
+    pub transfer_sequence: u64,
+    /// Recent sender balance readings, oldest first (at most `MAX_BALANCE_SAMPLES`), so adaptive
+    /// polling has an accrual rate right after a restart. Unreadable samples are dropped.
+    #[serde(deserialize_with = "lenient_samples")]
+    pub balance_samples: Vec<BalanceSample>,
+    /// Unix times of the recent confirmed sweeps, oldest first (at most `MAX_TRANSFER_TIMES`),
+    /// whose average interval `dry_spell_alert_multiple` compares the current gap against.
+    pub transfer_times: Vec<u64>,
+}
+
+/// One sender balance reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceSample {
+    pub lamports: u64,
+    /// Seconds since the Unix epoch.
+    pub unix_time: u64,
+}
+
+/// Samples are only a cache: a field written in another format is discarded (with a warning)
+/// instead of failing the whole state file.
+fn lenient_samples<'de, D>(deserializer: D) -> std::result::Result<Vec<BalanceSample>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).unwrap_or_else(|e| {
+        warn!("Discarding unreadable balance samples in state file: {}", e);
+        Vec::new()
+    }))
+}
+
+impl State {
+    /// Append a balance reading, dropping the oldest beyond `MAX_BALANCE_SAMPLES`.
+    pub fn push_balance_sample(&mut self, sample: BalanceSample) {
+        self.balance_samples.push(sample);
+        let excess = self
+            .balance_samples
+            .len()
+            .saturating_sub(MAX_BALANCE_SAMPLES);
+        self.balance_samples.drain(..excess);
+    }
+
+    /// Record a confirmed sweep at `unix_time`, dropping the oldest beyond `MAX_TRANSFER_TIMES`.
+    pub fn push_transfer_time(&mut self, unix_time: u64) {
+        self.transfer_times.push(unix_time);
+        let excess = self.transfer_times.len().saturating_sub(MAX_TRANSFER_TIMES);
+        self.transfer_times.drain(..excess);
+    }
+
+    /// Average seconds between the recorded sweeps; `None` with fewer than two intervals.
+    pub fn mean_transfer_interval(&self) -> Option<u64> {
+        let (first, last) = (self.transfer_times.first()?, self.transfer_times.last()?);
+        let intervals = self
+            .transfer_times
+            .len()
+            .checked_sub(1)
+            .filter(|n| *n >= 2)?;
+        Some(last.saturating_sub(*first) / intervals as u64)
+    }
+
+    /// Accrual rate in lamports per second from the latest balance increase among the samples.
+    /// Decreases are transfers or spending, not accrual.
+    pub fn accrual_rate(&self) -> Option<f64> {
+        self.balance_samples.windows(2).rev().find_map(|pair| {
+            let elapsed = pair[1].unix_time.saturating_sub(pair[0].unix_time);
+            (pair[1].lamports > pair[0].lamports && elapsed > 0)
+                .then(|| (pair[1].lamports - pair[0].lamports) as f64 / elapsed as f64)
+        })
+    }
+
+    /// Load the state file; a missing file yields the default state.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("parsing state file {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading state file {}", path.display())),
+        }
+    }
+
+    /// Atomically replace the state file (write to a temporary file, then rename).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing state file {}", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("replacing state file {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(lamports: u64, unix_time: u64) -> BalanceSample {
+        BalanceSample {
+            lamports,
+            unix_time,
+        }
+    }
+
+    #[test]
+    fn balance_samples_are_capped_and_give_the_latest_accrual_rate() {
+        let mut state = State::default();
+        for i in 0..MAX_BALANCE_SAMPLES as u64 + 4 {
+            state.push_balance_sample(sample(1_000 * i, 10 * i));
+        }
+        assert_eq!(state.balance_samples.len(), MAX_BALANCE_SAMPLES);
+        assert_eq!(state.balance_samples[0], sample(4_000, 40));
+        // A transfer (decrease) after the last increase does not reset the estimate
+        state.push_balance_sample(sample(0, 1_000));
+        assert_eq!(state.accrual_rate(), Some(100.0));
+    }
+
+    #[test]
+    fn transfer_times_are_capped_and_averaged() {
+        let mut state = State::default();
+        state.push_transfer_time(100);
+        state.push_transfer_time(200);
+        assert_eq!(state.mean_transfer_interval(), None);
+        for i in 0..MAX_TRANSFER_TIMES as u64 {
+            state.push_transfer_time(1_000 + 60 * i);
+        }
+        assert_eq!(state.transfer_times.len(), MAX_TRANSFER_TIMES);
+        assert_eq!(state.transfer_times[0], 1_000);
+        assert_eq!(state.mean_transfer_interval(), Some(60));
+    }
+
+    #[test]
+    fn older_or_foreign_state_files_still_load() {
+        let state: State = serde_json::from_str(r#"{"transfer_sequence": 3}"#).unwrap();
+        assert_eq!(state.transfer_sequence, 3);
+        assert!(state.balance_samples.is_empty());
+
+        let state: State = serde_json::from_str(
+            r#"{"transfer_sequence": 3, "balance_samples": [{"balance": 1}]}"#,
+        )
+        .unwrap();
+        assert_eq!(state.transfer_sequence, 3);
+        assert!(state.balance_samples.is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Logging setup, with optional OpenTelemetry (OTLP/HTTP) span export.
+
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use opentelemetry::{KeyValue, trace::TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+use std::io::{IsTerminal, Write};
+use tracing_subscriber::{EnvFilter, prelude::*};
+
+/// Initialize logging.
+///
+/// Without an OTLP endpoint this is the plain `env_logger` setup, with `deployment` on every
+/// line. With one, a `tracing` subscriber prints the same log lines to stdout and exports spans
+/// (one per poll cycle, with child spans per RPC call) to `otlp_endpoint`, e.g.
+/// `http://collector:4318/v1/traces`, tagged with a `deployment.name` resource attribute.
+/// The returned provider must be kept alive for spans to be exported.
+pub fn init(otlp_endpoint: Option<&str>, deployment: &str) -> Result<Option<SdkTracerProvider>> {
+    let Some(endpoint) = otlp_endpoint else {
+        let deployment = deployment.to_string();
+        env_logger::Builder::new()
+            .filter_level(LevelFilter::Info) // Set default level to INFO
+            .format(move |buf, record| {
+                let style = buf.default_level_style(record.level());
+                writeln!(
+                    buf,
+                    "[{} {style}{:<5}{style:#} {} {}] {}",
+                    buf.timestamp_seconds(),
+                    record.level(),
+                    deployment,
+                    record.target(),
+                    record.args()
+                )
+            })
+            .init();
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .context("building OTLP span exporter")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .with_attribute(KeyValue::new("deployment.name", deployment.to_string()))
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer().with_ansi(std::io::stdout().is_terminal()))
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .context("initializing tracing subscriber")?;
+    Ok(Some(provider))
+}
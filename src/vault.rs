@@ -0,0 +1,360 @@
+//! Secrets from HashiCorp Vault (`vault_addr`): the Slack webhook URL and the sender and fee
+//! payer keypairs can be read from a KV secrets engine at startup instead of the config file.
+//!
+//! Each `vault_*` setting is a `<path>#<key>` reference: `path` is the API path below `/v1/`
+//! (`secret/data/aft` for KV version 2, `secret/aft` for version 1) and `key` the field of the
+//! secret. The token comes from the environment (`vault_token_env`, default `VAULT_TOKEN`).
+//! Fetched values are only kept in memory. The token, each response body (when the HTTP client
+//! hands it over unshared) and the keypairs read from it are zeroized once dropped; the webhook
+//! URL is kept like one set in the config. Copies inside the HTTP and TLS stacks are out of reach.
+
+use anyhow::{Context, Result, anyhow};
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::{collections::HashMap, fmt, fmt::Write, time::Duration};
+use tracing::{info, warn};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{Config, DEFAULT_WEBHOOK_TIMEOUT_SECONDS, USER_AGENT};
+
+/// Environment variable holding the Vault token unless `vault_token_env` names another.
+const DEFAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+
+/// A secret value read from Vault, zeroized on drop and never printed.
+#[derive(Clone)]
+pub struct Secret {
+    /// The `<path>#<key>` reference it was read from, for error messages.
+    pub reference: String,
+    pub value: Zeroizing<String>,
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({}, [REDACTED])", self.reference)
+    }
+}
+
+/// Keypairs read from Vault; `None` where the keypair file applies.
+#[derive(Debug, Clone, Default)]
+pub struct Secrets {
+    pub sender_keypair: Option<Secret>,
+    pub fee_payer_keypair: Option<Secret>,
+}
+
+/// Fetch every secret `cfg` references from Vault: the webhook URL replaces `slack_webhook`,
+/// keypairs go to `cfg.vault_secrets`. Does nothing without `vault_addr`.
+pub async fn resolve(cfg: &mut Config) -> Result<()> {
+    let references = [
+        &cfg.vault_slack_webhook,
+        &cfg.vault_sender_keypair,
+        &cfg.vault_fee_payer_keypair,
+    ];
+    let Some(addr) = cfg.vault_addr.clone() else {
+        if references.iter().any(|r| r.is_some()) {
+            return Err(anyhow!(
+                "vault_slack_webhook, vault_sender_keypair and vault_fee_payer_keypair require vault_addr"
+            ));
+        }
+        return Ok(());
+    };
+    if references.iter().all(|r| r.is_none()) {
+        warn!("vault_addr is set but no vault_* secret is; nothing is read from Vault");
+        return Ok(());
+    }
+
+    let token_env = cfg.vault_token_env.as_deref().unwrap_or(DEFAULT_TOKEN_ENV);
+    let token = Zeroizing::new(std::env::var(token_env).with_context(|| {
+        format!(
+            "reading the Vault token from environment variable {}",
+            token_env
+        )
+    })?);
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(
+            cfg.webhook_timeout_seconds
+                .unwrap_or(DEFAULT_WEBHOOK_TIMEOUT_SECONDS),
+        ))
+        .build()
+        .context("building Vault HTTP client")?;
+
+    if let Some(reference) = &cfg.vault_slack_webhook {
+        let secret = fetch(&client, &addr, &token, reference).await?;
+        if cfg.slack_webhook.is_some() {
+            warn!("Both slack_webhook and vault_slack_webhook are set; using vault_slack_webhook");
+        }
+        cfg.slack_webhook = Some(secret.value.to_string());
+    }
+    if let Some(reference) = &cfg.vault_sender_keypair {
+        cfg.vault_secrets.sender_keypair = Some(fetch(&client, &addr, &token, reference).await?);
+    }
+    if let Some(reference) = &cfg.vault_fee_payer_keypair {
+        cfg.vault_secrets.fee_payer_keypair = Some(fetch(&client, &addr, &token, reference).await?);
+    }
+    Ok(())
+}
+
+/// Read the secret `reference` (`<path>#<key>`) from the Vault server at `addr`.
+async fn fetch(
+    client: &reqwest::Client,
+    addr: &str,
+    token: &str,
+    reference: &str,
+) -> Result<Secret> {
+    let (path, key) = reference.rsplit_once('#').ok_or_else(|| {
+        anyhow!(
+            "Vault secret reference {:?} must have the form <path>#<key>",
+            reference
+        )
+    })?;
+    let url = format!(
+        "{}/v1/{}",
+        addr.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    let resp = client
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .with_context(|| format!("reading Vault secret {}", path))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(anyhow!("Vault returned {} for secret {}", status, path));
+    }
+    let body = resp
+        .bytes()
+        .await
+        .with_context(|| format!("reading Vault response for secret {}", path))?;
+    // The body holds every field of the secret; clear it once the one needed is copied out
+    let value = match body.try_into_mut() {
+        Ok(mut body) => {
+            let value = secret_value(&body, key);
+            body.zeroize();
+            value
+        }
+        Err(shared) => secret_value(&shared, key),
+    }
+    .with_context(|| format!("parsing Vault response for secret {}", path))?
+    .ok_or_else(|| anyhow!("Vault secret {} has no string field {:?}", path, key))?;
+    info!("Read secret from Vault: reference = {}", reference);
+    Ok(Secret {
+        reference: reference.to_string(),
+        value,
+    })
+}
+
+/// Field `key` of the Vault read response `body`: KV version 2 nests the fields under
+/// `data.data`, version 1 under `data`. A keypair stored as a JSON array of bytes is returned as
+/// its JSON text.
+fn secret_value(body: &[u8], key: &str) -> Result<Option<Zeroizing<String>>> {
+    let response: ReadResponse = serde_json::from_slice(body)?;
+    let mut fields = response.data;
+    if let (Some(Field::Object(_)), Some(Field::Object(_))) =
+        (fields.get("data"), fields.get("metadata"))
+    {
+        let Some(Field::Object(nested)) = fields.remove("data") else {
+            unreachable!("checked above");
+        };
+        fields = nested;
+    }
+    Ok(match fields.remove(key) {
+        Some(Field::Text(text)) => Some(text),
+        Some(Field::Bytes(bytes)) => {
+            // Sized up front so the text is never reallocated, which would leave a copy behind
+            let mut text = Zeroizing::new(String::with_capacity(bytes.len() * 4 + 2));
+            text.push('[');
+            for (i, byte) in bytes.iter().enumerate() {
+                if i > 0 {
+                    text.push(',');
+                }
+                write!(text, "{}", byte).expect("writing to a String cannot fail");
+            }
+            text.push(']');
+            Some(text)
+        }
+        _ => None,
+    })
+}
+
+/// A Vault read response, keeping only what a secret field can be.
+#[derive(serde::Deserialize)]
+struct ReadResponse {
+    data: HashMap<String, Field>,
+}
+
+/// A field of a Vault secret. Strings and byte arrays are deserialized straight into zeroized
+/// buffers, without the intermediate copies a `serde_json::Value` would leave.
+enum Field {
+    Text(Zeroizing<String>),
+    Bytes(Zeroizing<Vec<u8>>),
+    Object(HashMap<String, Field>),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a Vault secret field")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+        Ok(Field::Text(Zeroizing::new(v.to_owned())))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Field, E> {
+        Ok(Field::Text(Zeroizing::new(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Field, A::Error> {
+        // Keypair-sized, so a keypair is never reallocated
+        let mut bytes = Zeroizing::new(Vec::with_capacity(64));
+        let mut all_bytes = true;
+        while let Some(element) = seq.next_element::<Element>()? {
+            match element {
+                Element::Byte(byte) => bytes.push(byte),
+                Element::Other => all_bytes = false,
+            }
+        }
+        Ok(if all_bytes {
+            Field::Bytes(bytes)
+        } else {
+            Field::Other
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Field, A::Error> {
+        let mut fields = HashMap::new();
+        while let Some((key, value)) = map.next_entry::<String, Field>()? {
+            fields.insert(key, value);
+        }
+        Ok(Field::Object(fields))
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Field, E> {
+        Ok(Field::Other)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Field, E> {
+        Ok(Field::Other)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Field, E> {
+        Ok(Field::Other)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Field, E> {
+        Ok(Field::Other)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Field, E> {
+        Ok(Field::Other)
+    }
+}
+
+/// An element of an array field: a byte of a keypair, or anything else.
+enum Element {
+    Byte(u8),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ElementVisitor)
+    }
+}
+
+struct ElementVisitor;
+
+impl<'de> Visitor<'de> for ElementVisitor {
+    type Value = Element;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array element")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Element, E> {
+        Ok(u8::try_from(v).map_or(Element::Other, Element::Byte))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Element, E> {
+        Ok(u8::try_from(v).map_or(Element::Other, Element::Byte))
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Element, E> {
+        Ok(Element::Other)
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Element, E> {
+        Ok(Element::Other)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Element, E> {
+        Ok(Element::Other)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Element, E> {
+        Ok(Element::Other)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Element, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Element::Other)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Element, A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(Element::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secret_fields_are_read_from_kv_v1_and_v2_responses() {
+        let secret_value =
+            |body: &serde_json::Value, key| secret_value(body.to_string().as_bytes(), key).unwrap();
+        let v2 = json!({"data": {
+            "data": {
+                "slack_webhook": "https://hooks.slack.com/services/X",
+                "sender": [1, 2],
+                "mixed": [1, "a", 300],
+                "count": 4,
+            },
+            "metadata": {"version": 3},
+        }});
+        assert_eq!(
+            secret_value(&v2, "slack_webhook")
+                .as_deref()
+                .map(String::as_str),
+            Some("https://hooks.slack.com/services/X")
+        );
+        assert_eq!(
+            secret_value(&v2, "sender").as_deref().map(String::as_str),
+            Some("[1,2]")
+        );
+        assert!(secret_value(&v2, "missing").is_none());
+        assert!(secret_value(&v2, "mixed").is_none());
+        assert!(secret_value(&v2, "count").is_none());
+
+        let v1 = json!({"data": {"slack_webhook": "https://hooks.slack.com/services/Y"}});
+        assert_eq!(
+            secret_value(&v1, "slack_webhook")
+                .as_deref()
+                .map(String::as_str),
+            Some("https://hooks.slack.com/services/Y")
+        );
+    }
+}